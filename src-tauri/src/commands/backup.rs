@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+use super::error::CommandError;
+use super::file::content_hash;
+use super::paths::IpcPath;
+use super::sandbox::{check_access, workspace_of};
+use super::settings::{update_workspace_config, workspace_config};
+use crate::WorkspaceAccess;
+
+/// Most backups a workspace can ask to keep per file.
+const MAX_BACKUPS: usize = 100;
+
+fn backups_dir(app: &AppHandle) -> Result<PathBuf, CommandError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+
+    Ok(data_dir.join("backups"))
+}
+
+/// Delete all but the newest `keep` backups in `dir`. Backup names start with
+/// a fixed-width timestamp, so they sort oldest first.
+fn rotate_backups(dir: &Path, keep: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut backups: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .map(|entry| entry.path())
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    for backup in backups.drain(..excess) {
+        let _ = fs::remove_file(backup);
+    }
+}
+
+/// Copy the current content of `path` into the backups of `dir`, keeping the newest `keep`.
+pub(crate) fn back_up_into(dir: &Path, path: &Path, keep: usize) -> Result<(), CommandError> {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    fs::create_dir_all(dir)
+        .map_err(|e| CommandError::io(dir, "Failed to create backup directory", e))?;
+    let backup = dir.join(format!("{:020}-{}", millis, name));
+    fs::copy(path, &backup).map_err(|e| CommandError::io(&backup, "Failed to back up file", e))?;
+
+    rotate_backups(dir, keep);
+    Ok(())
+}
+
+/// Keep a copy of the file at `path` before it is replaced, when its workspace
+/// has backups turned on.
+///
+/// Backups live in the app data directory, one folder per workspace and file,
+/// so they never show up in the workspace itself.
+pub(crate) fn back_up_before_save(app: &AppHandle, path: &Path) -> Result<(), CommandError> {
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    let Ok((root, relative)) = workspace_of(&app.state::<WorkspaceAccess>(), path) else {
+        return Ok(());
+    };
+
+    let keep = workspace_config(app, &root)?.backups;
+    if keep == 0 {
+        return Ok(());
+    }
+
+    let dir = backups_dir(app)?
+        .join(content_hash(root.as_os_str().as_encoded_bytes()))
+        .join(relative);
+    back_up_into(&dir, path, keep)
+}
+
+/// How many earlier versions of each file are kept as backups in a workspace; 0 when off.
+#[tauri::command]
+pub async fn get_workspace_backups(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    root: IpcPath,
+) -> Result<usize, CommandError> {
+    let root = root.to_path_buf();
+    check_access(&access, &root)?;
+    let (root, _) = workspace_of(&access, &root)?;
+
+    Ok(workspace_config(&app, &root)?.backups)
+}
+
+/// Keep the last `keep` versions of each file in a workspace as backups, replaced
+/// on every save; 0 turns backups off. Existing backups are left in place.
+#[tauri::command]
+pub async fn set_workspace_backups(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    root: IpcPath,
+    keep: usize,
+) -> Result<(), CommandError> {
+    update_workspace_config(&app, &access, &root.to_path_buf(), |config| {
+        config.backups = keep.min(MAX_BACKUPS);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::back_up_into;
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+
+    #[test]
+    fn back_up_into_keeps_only_the_newest_backups() {
+        let root = make_temp_dir("backups");
+        let file_path = root.join("note.md");
+        let backups = root.join("backups").join("note.md");
+
+        for version in ["one", "two", "three"] {
+            fs::write(&file_path, version).expect("failed to write file");
+            back_up_into(&backups, &file_path, 2).expect("backup should be written");
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let mut kept: Vec<String> = fs::read_dir(&backups)
+            .expect("backup directory should exist")
+            .flatten()
+            .map(|entry| fs::read_to_string(entry.path()).expect("backup should be readable"))
+            .collect();
+        kept.sort();
+        assert_eq!(kept, ["three", "two"]);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,139 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// How a text file is stored on disk: its character encoding and whether it
+/// starts with a byte order mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TextFormat {
+    pub(crate) encoding: &'static Encoding,
+    pub(crate) bom: bool,
+}
+
+impl TextFormat {
+    pub(crate) const UTF8: TextFormat = TextFormat {
+        encoding: UTF_8,
+        bom: false,
+    };
+
+    /// Look up an encoding by any of its WHATWG labels, e.g. `utf-8`,
+    /// `latin1` or `utf-16le`. UTF-16 gets a BOM, since it cannot be
+    /// recognised without one; other encodings do not.
+    pub(crate) fn from_label(label: &str) -> Result<Self, String> {
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| format!("Unsupported encoding: {}", label))?;
+
+        Ok(Self {
+            encoding,
+            bom: is_utf16(encoding),
+        })
+    }
+
+    /// Canonical name of the encoding, as reported to the frontend.
+    pub(crate) fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    /// Encode `content` for writing, refusing characters the encoding cannot represent.
+    pub(crate) fn encode(&self, content: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::with_capacity(content.len() + 3);
+
+        if is_utf16(self.encoding) {
+            let little_endian = self.encoding == UTF_16LE;
+            if self.bom {
+                bytes.extend_from_slice(if little_endian { &[0xFF, 0xFE] } else { &[0xFE, 0xFF] });
+            }
+            for unit in content.encode_utf16() {
+                let pair = if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() };
+                bytes.extend_from_slice(&pair);
+            }
+            return Ok(bytes);
+        }
+
+        if self.encoding == UTF_8 {
+            if self.bom {
+                bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+            }
+            bytes.extend_from_slice(content.as_bytes());
+            return Ok(bytes);
+        }
+
+        if self.encoding.output_encoding() != self.encoding {
+            return Err(format!("Cannot save files as {}", self.name()));
+        }
+
+        let (encoded, _, had_errors) = self.encoding.encode(content);
+        if had_errors {
+            return Err(format!(
+                "The document contains characters that cannot be saved as {}; convert it to UTF-8 first",
+                self.name()
+            ));
+        }
+
+        bytes.extend_from_slice(&encoded);
+        Ok(bytes)
+    }
+}
+
+fn is_utf16(encoding: &'static Encoding) -> bool {
+    encoding == UTF_16LE || encoding == UTF_16BE
+}
+
+/// Decode file content, detecting its encoding.
+///
+/// A byte order mark wins; otherwise valid UTF-8 is taken as UTF-8 and anything
+/// else is guessed from the bytes, falling back to Windows-1252. Decoding never
+/// fails: malformed sequences become replacement characters.
+pub(crate) fn decode_text(bytes: &[u8]) -> (String, TextFormat) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        let (content, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        return (content.into_owned(), TextFormat { encoding, bom: true });
+    }
+
+    if let Ok(content) = std::str::from_utf8(bytes) {
+        return (content.to_string(), TextFormat::UTF8);
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let encoding = detector.guess(None, true);
+
+    let (content, _) = encoding.decode_without_bom_handling(bytes);
+    (content.into_owned(), TextFormat { encoding, bom: false })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_text, TextFormat};
+    use encoding_rs::{UTF_16LE, WINDOWS_1252};
+
+    #[test]
+    fn decode_text_honours_boms_and_round_trips_utf16() {
+        let bytes = [0xFF, 0xFE, b'#', 0, b' ', 0, 0xE9, 0, b'\n', 0];
+        let (content, format) = decode_text(&bytes);
+
+        assert_eq!(content, "# é\n");
+        assert_eq!(format, TextFormat { encoding: UTF_16LE, bom: true });
+        assert_eq!(format.encode(&content).expect("utf-16 should encode"), bytes);
+
+        let (content, format) = decode_text(b"\xEF\xBB\xBFplain");
+        assert_eq!(content, "plain");
+        assert!(format.bom);
+        assert_eq!(format.encode("plain").expect("utf-8 should encode"), b"\xEF\xBB\xBFplain");
+    }
+
+    #[test]
+    fn decode_text_detects_legacy_encodings_and_refuses_unmappable_characters() {
+        let bytes = b"Caf\xe9 cr\xe8me br\xfbl\xe9e, na\xefve fa\xe7ade.\n";
+        let (content, format) = decode_text(bytes);
+
+        assert_eq!(content, "Café crème brûlée, naïve façade.\n");
+        assert_eq!(format.encoding, WINDOWS_1252);
+        assert_eq!(format.encode(&content).expect("latin text should encode"), bytes.to_vec());
+        assert!(format.encode("snowman ☃").is_err());
+
+        let latin1 = TextFormat::from_label("latin1").expect("label should resolve");
+        assert_eq!(latin1.encoding, WINDOWS_1252);
+        assert!(TextFormat::from_label("utf-16").expect("label should resolve").bom);
+        assert!(TextFormat::from_label("klingon").is_err());
+    }
+}
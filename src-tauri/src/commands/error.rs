@@ -0,0 +1,177 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use super::paths::IpcPath;
+
+/// Error returned by file commands.
+///
+/// It reaches the frontend as `{ kind, message, path?, code? }`: `kind` is a
+/// stable snake_case identifier to branch on and localise, `message` an English
+/// description for logs, `path` the item concerned and `code` the OS error code
+/// for `io` errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    NotFound(IpcPath),
+    AlreadyExists(IpcPath),
+    PermissionDenied(IpcPath),
+    /// The item cannot be put back because another one took its place.
+    Conflict(IpcPath),
+    /// Saving would replace a file that has content with an empty document.
+    EmptyOverwrite(IpcPath),
+    InvalidName(String),
+    Io {
+        path: Option<IpcPath>,
+        message: String,
+        code: Option<i32>,
+    },
+    /// The user dismissed a dialog.
+    Cancelled,
+    /// The path resolves outside every folder and file the user opened.
+    OutOfWorkspace(IpcPath),
+    /// Any failure not covered by the kinds above, such as an unsupported encoding.
+    Other(String),
+}
+
+impl CommandError {
+    /// Classify an I/O error on `path`, keeping `context` and the OS error code
+    /// for errors without a kind of their own.
+    pub(crate) fn io(path: &Path, context: &str, error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => CommandError::NotFound(IpcPath::from(path)),
+            io::ErrorKind::AlreadyExists => CommandError::AlreadyExists(IpcPath::from(path)),
+            io::ErrorKind::PermissionDenied => CommandError::PermissionDenied(IpcPath::from(path)),
+            _ => CommandError::Io {
+                path: Some(IpcPath::from(path)),
+                message: format!("{}: {}", context, error),
+                code: error.raw_os_error(),
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CommandError::NotFound(_) => "not_found",
+            CommandError::AlreadyExists(_) => "already_exists",
+            CommandError::PermissionDenied(_) => "permission_denied",
+            CommandError::Conflict(_) => "conflict",
+            CommandError::EmptyOverwrite(_) => "empty_overwrite",
+            CommandError::InvalidName(_) => "invalid_name",
+            CommandError::Io { .. } => "io",
+            CommandError::Cancelled => "cancelled",
+            CommandError::OutOfWorkspace(_) => "out_of_workspace",
+            CommandError::Other(_) => "other",
+        }
+    }
+
+    pub fn path(&self) -> Option<&IpcPath> {
+        match self {
+            CommandError::NotFound(path)
+            | CommandError::AlreadyExists(path)
+            | CommandError::PermissionDenied(path)
+            | CommandError::Conflict(path)
+            | CommandError::EmptyOverwrite(path)
+            | CommandError::OutOfWorkspace(path) => Some(path),
+            CommandError::Io { path, .. } => path.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::NotFound(path) => write!(f, "{} does not exist", path.display()),
+            CommandError::AlreadyExists(path) => write!(f, "{} already exists", path.display()),
+            CommandError::PermissionDenied(path) => write!(f, "Permission denied: {}", path.display()),
+            CommandError::Conflict(path) => {
+                write!(f, "Another item is in the way of {}", path.display())
+            }
+            CommandError::EmptyOverwrite(path) => {
+                write!(f, "Refusing to replace {} with an empty document", path.display())
+            }
+            CommandError::InvalidName(name) => write!(f, "Invalid name: {:?}", name),
+            CommandError::Io { message, .. } => f.write_str(message),
+            CommandError::Cancelled => f.write_str("Cancelled"),
+            CommandError::OutOfWorkspace(path) => {
+                write!(f, "Access denied: {} is outside the opened workspace", path.display())
+            }
+            CommandError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+#[derive(Serialize)]
+struct ErrorPayload<'a> {
+    kind: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a IpcPath>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<i32>,
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let code = match self {
+            CommandError::Io { code, .. } => *code,
+            _ => None,
+        };
+
+        ErrorPayload {
+            kind: self.kind(),
+            message: self.to_string(),
+            path: self.path(),
+            code,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Other(message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        CommandError::Other(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandError;
+    use std::io;
+    use std::path::Path;
+
+    #[test]
+    fn io_errors_are_classified_and_serialised_with_their_kind() {
+        let path = Path::new("/notes/a.md");
+
+        let missing = CommandError::io(path, "Failed to read file", io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(missing, CommandError::NotFound(path.into()));
+        assert_eq!(
+            serde_json::to_value(&missing).expect("error should serialise"),
+            serde_json::json!({
+                "kind": "not_found",
+                "message": "/notes/a.md does not exist",
+                "path": "/notes/a.md",
+            })
+        );
+
+        let busy = CommandError::io(path, "Failed to rename", io::Error::from_raw_os_error(16));
+        let value = serde_json::to_value(&busy).expect("error should serialise");
+        assert_eq!(value["kind"], "io");
+        assert_eq!(value["code"], 16);
+        assert!(value["message"].as_str().is_some_and(|m| m.starts_with("Failed to rename: ")));
+
+        assert_eq!(
+            serde_json::to_value(CommandError::Cancelled).expect("error should serialise"),
+            serde_json::json!({ "kind": "cancelled", "message": "Cancelled" })
+        );
+    }
+}
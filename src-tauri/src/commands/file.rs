@@ -1,56 +1,271 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::io::Write;
-use std::path::Path;
-use std::sync::atomic::Ordering;
-use std::thread;
-use std::time::{Duration, SystemTime};
-use tauri::{AppHandle, Emitter, State};
-use crate::FileWatchRegistry;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::ipc::Response;
+use tauri::{AppHandle, Manager, State};
+use super::backup::back_up_before_save;
+use super::encoding::{decode_text, TextFormat};
+use super::error::CommandError;
+use super::history::{move_history, record_version};
+use super::line_endings::{apply_line_style, detect_line_style, preserve_line_style, LineEnding};
+use super::ignore::IgnoreRules;
+use super::index::index_own_write;
+use super::journal::{record_operation, FileOperation};
+use super::links::{update_links_after_move, LinkEdit, LinkUpdate};
+use super::paths::IpcPath;
+use super::quick_open::invalidate_quick_open_containing;
+use super::sandbox::{check_access, check_entry_access, check_linked_image, grant_access};
+use super::settings::settings_for;
+use super::watch::record_own_write;
+use crate::{
+    FileOperationJournal, FileWatchRegistry, QuickOpenRegistry, SearchIndexRegistry, WorkspaceAccess,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileData {
-    pub path: String,
+    pub path: IpcPath,
     pub content: String,
     pub name: String,
+    pub modified: Option<u64>,
+    /// Hash of the bytes on disk, whatever their encoding.
+    pub hash: String,
+    /// Encoding the file is stored in, e.g. `UTF-8`, `windows-1252` or `UTF-16LE`.
+    pub encoding: String,
+    /// Whether the file starts with a byte order mark.
+    pub bom: bool,
+    /// Predominant line ending of the file.
+    pub line_ending: LineEnding,
+    /// Whether the file ends with a line break.
+    pub final_newline: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SaveResult {
-    pub path: String,
+    pub path: IpcPath,
     pub name: String,
+    pub modified: Option<u64>,
+    /// Hash of the bytes written, to pass as `expected_hash` on the next save.
+    pub hash: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How `save_markdown_file` should encode a document. Fields left out keep
+/// what the file has on disk, so a workspace policy only needs to set the
+/// fields it enforces.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SaveFormat {
+    /// Any WHATWG encoding label, e.g. `utf-8` to convert a legacy file.
+    pub encoding: Option<String>,
+    pub bom: Option<bool>,
+    /// Convert every line to this ending; when left out, unchanged lines keep
+    /// the ending they have on disk.
+    pub line_ending: Option<LineEnding>,
+    pub final_newline: Option<bool>,
+}
+
+impl SaveFormat {
+    /// This format, with the fields it leaves out taken from `fallback`.
+    pub(crate) fn or(self, fallback: SaveFormat) -> SaveFormat {
+        SaveFormat {
+            encoding: self.encoding.or(fallback.encoding),
+            bom: self.bom.or(fallback.bom),
+            line_ending: self.line_ending.or(fallback.line_ending),
+            final_newline: self.final_newline.or(fallback.final_newline),
+        }
+    }
+}
+
+/// Outcome of an optimistic-concurrency save.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SaveOutcome {
+    Saved {
+        modified: Option<u64>,
+        hash: String,
+    },
+    /// The file changed on disk since the frontend last saw it; nothing was written.
+    Conflict {
+        content: String,
+        modified: Option<u64>,
+        hash: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileEntry {
     pub name: String,
-    pub path: String,
+    pub path: IpcPath,
     pub is_dir: bool,
     pub is_markdown: bool,
+    /// Matched by an ignore rule; only listed when ignored items are shown.
+    pub ignored: bool,
+    /// Size in bytes; zero for directories.
+    pub size: u64,
+    /// Creation and modification times in milliseconds since the Unix epoch,
+    /// where the filesystem records them.
+    pub created: Option<u64>,
+    pub modified: Option<u64>,
+    pub readonly: bool,
+    /// Where the entry points when it is a symbolic link.
+    pub symlink_target: Option<String>,
+    /// Number of listed items in a directory, even when `children` is not loaded yet.
+    pub child_count: Option<usize>,
     pub children: Option<Vec<FileEntry>>,
 }
 
+/// What directory listings are ordered by.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Alphabetical, ignoring case.
+    #[default]
+    Name,
+    /// Most recently modified first.
+    Modified,
+    /// Largest first.
+    Size,
+}
+
+/// Ordering of directory listings. Ties are broken by name.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct DirectorySort {
+    pub by: SortKey,
+    /// List folders before files instead of mixing them.
+    pub folders_first: bool,
+}
+
+impl Default for DirectorySort {
+    fn default() -> Self {
+        Self {
+            by: SortKey::Name,
+            folders_first: true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoveResult {
+    pub path: IpcPath,
+    /// Files whose links were rewritten to follow the item.
+    pub updated_files: Vec<LinkUpdate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteResult {
+    pub path: IpcPath,
+    /// Whether the item went to the OS trash rather than being removed permanently.
+    pub trashed: bool,
+    /// Trash entry to pass to `restore_deleted_item`, where the platform supports restoring.
+    pub trash_id: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FolderData {
-    pub path: String,
+    pub path: IpcPath,
     pub name: String,
     pub entries: Vec<FileEntry>,
+    pub warnings: Vec<ListingWarning>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct FileWatchEvent {
-    pub path: String,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirectoryContents {
+    pub entries: Vec<FileEntry>,
+    pub warnings: Vec<ListingWarning>,
+}
+
+/// An entry left out of a directory listing because it could not be read.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ListingWarning {
+    pub path: IpcPath,
+    /// `permission_denied`, `broken_symlink`, `symlink_loop` or `unreadable`.
     pub kind: String,
+    pub message: String,
+}
+
+/// State threaded through a recursive directory listing.
+pub(crate) struct DirectoryListing {
+    pub(crate) rules: IgnoreRules,
+    sort: DirectorySort,
+    pub(crate) warnings: Vec<ListingWarning>,
+}
+
+impl DirectoryListing {
+    pub(crate) fn new(root: &Path, show_ignored: bool, sort: DirectorySort) -> Self {
+        Self {
+            rules: IgnoreRules::new(root, show_ignored),
+            sort,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn warn(&mut self, path: &Path, kind: &str, message: impl std::fmt::Display) {
+        self.warnings.push(ListingWarning {
+            path: IpcPath::from(path),
+            kind: kind.to_string(),
+            message: message.to_string(),
+        });
+    }
+}
+
+/// Give the replacement written by `atomic_write_file` the original file's
+/// ownership, permissions and extended attributes. Ownership and attributes are
+/// best effort, since changing them can need privileges Kea does not have.
+fn copy_file_attributes(
+    original: &Path,
+    replacement: &fs::File,
+    replacement_path: &Path,
+) -> Result<(), CommandError> {
+    let Ok(metadata) = fs::metadata(original) else {
+        return Ok(());
+    };
+
+    // Ownership first: changing the owner can clear set-id mode bits.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{fchown, MetadataExt};
+
+        if fchown(replacement, Some(metadata.uid()), Some(metadata.gid())).is_err() {
+            let _ = fchown(replacement, None, Some(metadata.gid()));
+        }
+    }
+
+    replacement
+        .set_permissions(metadata.permissions())
+        .map_err(|e| CommandError::io(replacement_path, "Failed to copy file permissions", e))?;
+
+    copy_extended_attributes(original, replacement_path);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_extended_attributes(original: &Path, replacement: &Path) {
+    let Ok(names) = xattr::list(original) else {
+        return;
+    };
+
+    for name in names {
+        if let Ok(Some(value)) = xattr::get(original, &name) {
+            let _ = xattr::set(replacement, &name, &value);
+        }
+    }
 }
 
-fn atomic_write_file(path: &Path, content: &str) -> Result<(), String> {
+#[cfg(not(unix))]
+fn copy_extended_attributes(_original: &Path, _replacement: &Path) {}
+
+pub(crate) fn atomic_write_file(path: &Path, content: impl AsRef<[u8]>) -> Result<(), CommandError> {
     let parent = path
         .parent()
-        .ok_or("Invalid file path: missing parent directory")?;
+        .ok_or_else(|| CommandError::InvalidName(path.display().to_string()))?;
 
     if !parent.exists() {
         fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+            .map_err(|e| CommandError::io(parent, "Failed to create parent directory", e))?;
     }
 
     let file_name = path
@@ -65,17 +280,19 @@ fn atomic_write_file(path: &Path, content: &str) -> Result<(), String> {
 
     let temp_path = parent.join(format!(".{}.kea.{}.tmp", file_name, timestamp));
 
-    let write_result = (|| -> Result<(), String> {
+    let write_result = (|| -> Result<(), CommandError> {
         let mut temp_file = fs::File::create(&temp_path)
-            .map_err(|e| format!("Failed to create temp file: {}", e))?;
+            .map_err(|e| CommandError::io(&temp_path, "Failed to create temp file", e))?;
 
         temp_file
-            .write_all(content.as_bytes())
-            .map_err(|e| format!("Failed to write temp file: {}", e))?;
+            .write_all(content.as_ref())
+            .map_err(|e| CommandError::io(&temp_path, "Failed to write temp file", e))?;
+
+        copy_file_attributes(path, &temp_file, &temp_path)?;
 
         temp_file
             .sync_all()
-            .map_err(|e| format!("Failed to flush temp file: {}", e))?;
+            .map_err(|e| CommandError::io(&temp_path, "Failed to flush temp file", e))?;
 
         Ok(())
     })();
@@ -91,11 +308,11 @@ fn atomic_write_file(path: &Path, content: &str) -> Result<(), String> {
 
     if path.exists() {
         fs::remove_file(path)
-            .map_err(|e| format!("Failed to replace existing file: {}", e))?;
+            .map_err(|e| CommandError::io(path, "Failed to replace existing file", e))?;
     }
 
     fs::rename(&temp_path, path)
-        .map_err(|e| format!("Failed to move temp file into place: {}", e))?;
+        .map_err(|e| CommandError::io(path, "Failed to move temp file into place", e))?;
 
     Ok(())
 }
@@ -104,9 +321,150 @@ fn read_modified_time(path: &Path) -> Option<SystemTime> {
     fs::metadata(path).ok()?.modified().ok()
 }
 
+/// Milliseconds since the Unix epoch, as timestamps are sent to the frontend.
+fn system_time_millis(time: SystemTime) -> Option<u64> {
+    let millis = time.duration_since(UNIX_EPOCH).ok()?.as_millis();
+    u64::try_from(millis).ok()
+}
+
+/// Modification time in milliseconds since the Unix epoch, as sent to the frontend.
+pub(crate) fn read_modified_millis(path: &Path) -> Option<u64> {
+    system_time_millis(read_modified_time(path)?)
+}
+
+/// Hex-encoded SHA-256 of file content.
+pub(crate) fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Read a text file in whatever encoding it is stored in.
+pub(crate) fn read_document(path: &Path) -> Result<FileData, CommandError> {
+    let bytes = fs::read(path).map_err(|e| CommandError::io(path, "Failed to read file", e))?;
+    let (content, format) = decode_text(&bytes);
+    let lines = detect_line_style(&content);
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Untitled".to_string());
+
+    Ok(FileData {
+        path: IpcPath::from(path),
+        content,
+        name,
+        modified: read_modified_millis(path),
+        hash: content_hash(&bytes),
+        encoding: format.name().to_string(),
+        bom: format.bom,
+        line_ending: lines.line_ending,
+        final_newline: lines.final_newline,
+    })
+}
+
+/// Bring `content` into the encoding and line style it should be saved with,
+/// returning the normalised text and the bytes to write.
+///
+/// Anything `requested` leaves out follows the file on disk, with unchanged
+/// lines keeping their own line ending; for a new file, the encoding is UTF-8
+/// and the content's own line style is kept.
+fn encode_for_save(
+    path: &Path,
+    content: &str,
+    requested: SaveFormat,
+) -> Result<(String, Vec<u8>), CommandError> {
+    let (mut format, mut lines, stored) = match fs::read(path) {
+        Ok(bytes) => {
+            let (stored, format) = decode_text(&bytes);
+            (format, detect_line_style(&stored), Some(stored))
+        }
+        Err(_) => (TextFormat::UTF8, detect_line_style(content), None),
+    };
+
+    if let Some(label) = requested.encoding {
+        format = TextFormat::from_label(&label)?;
+    }
+    if let Some(bom) = requested.bom {
+        format.bom = bom;
+    }
+    if let Some(final_newline) = requested.final_newline {
+        lines.final_newline = final_newline;
+    }
+
+    let content = match (requested.line_ending, stored) {
+        (Some(line_ending), _) => {
+            lines.line_ending = line_ending;
+            apply_line_style(content, lines)
+        }
+        (None, Some(stored)) => preserve_line_style(content, &stored, lines),
+        (None, None) => apply_line_style(content, lines),
+    };
+    let bytes = format.encode(&content)?;
+    Ok((content, bytes))
+}
+
+/// Refuse to replace a file that has content with an empty document unless
+/// `force` is set: that is far more often an editor bug than intent.
+fn check_not_emptying(path: &Path, content: &str, force: bool) -> Result<(), CommandError> {
+    if force || !content.is_empty() {
+        return Ok(());
+    }
+
+    let has_content = fs::read(path).is_ok_and(|bytes| !decode_text(&bytes).0.is_empty());
+    if has_content {
+        return Err(CommandError::EmptyOverwrite(IpcPath::from(path)));
+    }
+
+    Ok(())
+}
+
+/// Write already-encoded `content` unless the file on disk no longer matches
+/// what the caller last saw. `before_write` runs once the save is known to go
+/// ahead, right before the file is replaced.
+///
+/// The hash is authoritative when given; the modification time is only compared
+/// when no hash was supplied. A missing file is never a conflict.
+fn save_if_unchanged(
+    path: &Path,
+    content: &[u8],
+    expected_modified: Option<u64>,
+    expected_hash: Option<&str>,
+    before_write: impl FnOnce() -> Result<(), CommandError>,
+) -> Result<SaveOutcome, CommandError> {
+    if path.exists() && (expected_modified.is_some() || expected_hash.is_some()) {
+        let current_bytes = fs::read(path)
+            .map_err(|e| CommandError::io(path, "Failed to read file", e))?;
+        let current_hash = content_hash(&current_bytes);
+        let current_modified = read_modified_millis(path);
+
+        let changed = match expected_hash {
+            Some(hash) => hash != current_hash,
+            None => expected_modified != current_modified,
+        };
+
+        if changed {
+            return Ok(SaveOutcome::Conflict {
+                content: decode_text(&current_bytes).0,
+                modified: current_modified,
+                hash: current_hash,
+            });
+        }
+    }
+
+    before_write()?;
+    atomic_write_file(path, content)?;
+
+    Ok(SaveOutcome::Saved {
+        modified: read_modified_millis(path),
+        hash: content_hash(content),
+    })
+}
+
 /// Open a markdown file using file picker
 #[tauri::command]
-pub async fn open_markdown_file(app: AppHandle) -> Result<FileData, String> {
+pub async fn open_markdown_file(app: AppHandle) -> Result<FileData, CommandError> {
     use tauri_plugin_dialog::DialogExt;
 
     // Open file picker dialog
@@ -123,44 +481,124 @@ pub async fn open_markdown_file(app: AppHandle) -> Result<FileData, String> {
                 .into_path()
                 .map_err(|_| "Invalid file path")?;
 
-            // Read file content
-            let content = fs::read_to_string(&path)
-                .map_err(|e| format!("Failed to read file: {}", e))?;
-
-            // Get file name
-            let name = path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("Untitled")
-                .to_string();
-
-            // Convert path to string
-            let path_str = path
-                .to_str()
-                .ok_or("Invalid file path")?
-                .to_string();
-
-            Ok(FileData {
-                path: path_str,
-                content,
-                name,
-            })
+            grant_access(&app, &path)?;
+            read_document(&path)
         }
-        None => Err("No file selected".to_string()),
+        None => Err(CommandError::Cancelled),
     }
 }
 
-/// Save markdown content to specified file path
+/// Save markdown content to specified file path.
+///
+/// When `expected_modified` or `expected_hash` is given, the save is refused with
+/// a `conflict` outcome if the file changed on disk since the frontend loaded it.
+///
+/// The file keeps the encoding, byte order mark, line endings and final-newline
+/// state it has on disk unless `format` or the workspace's saved format says
+/// otherwise. New files are written as UTF-8.
+///
+/// Emptying a file that has content is refused unless `force` is set. When the
+/// workspace keeps backups, the previous version is backed up first.
 #[tauri::command]
-pub async fn save_markdown_file(path: String, content: String) -> Result<(), String> {
-    atomic_write_file(Path::new(&path), &content)?;
+pub async fn save_markdown_file(
+    app: AppHandle,
+    path: IpcPath,
+    content: String,
+    expected_modified: Option<u64>,
+    expected_hash: Option<String>,
+    format: Option<SaveFormat>,
+    force: Option<bool>,
+) -> Result<SaveOutcome, CommandError> {
+    let path = path.to_path_buf();
+    check_access(&app.state::<WorkspaceAccess>(), &path)?;
+
+    let (outcome, _) = save_document(
+        &app,
+        &path,
+        &content,
+        expected_modified,
+        expected_hash.as_deref(),
+        format.unwrap_or_default(),
+        force.unwrap_or(false),
+    )?;
+
+    Ok(outcome)
+}
 
-    Ok(())
+/// Save `content` to `path` the way `save_markdown_file` does: in the file's
+/// own encoding and line style unless `format` or the workspace says otherwise,
+/// refusing stale saves, and with the backup, history and index kept in step.
+/// Returns the outcome and the text as written. Callers check access first.
+pub(crate) fn save_document(
+    app: &AppHandle,
+    path: &Path,
+    content: &str,
+    expected_modified: Option<u64>,
+    expected_hash: Option<&str>,
+    format: SaveFormat,
+    force: bool,
+) -> Result<(SaveOutcome, String), CommandError> {
+    let format = format.or(settings_for(app, path).save_format);
+    let (content, bytes) = encode_for_save(path, content, format)?;
+    check_not_emptying(path, &content, force)?;
+
+    let outcome = write_document(app, path, &content, &bytes, expected_modified, expected_hash)?;
+    Ok((outcome, content))
+}
+
+/// Put `bytes`, an earlier version of `path`, back through the same steps as
+/// `save_document`. They are written as they were unless the workspace enforces
+/// a save format. An empty version may be restored: the user picked it.
+pub(crate) fn restore_document(
+    app: &AppHandle,
+    path: &Path,
+    bytes: &[u8],
+    expected_hash: Option<&str>,
+) -> Result<SaveOutcome, CommandError> {
+    let (content, _) = decode_text(bytes);
+    let enforced = settings_for(app, path).save_format;
+    if enforced == SaveFormat::default() {
+        return write_document(app, path, &content, bytes, None, expected_hash);
+    }
+
+    let (content, bytes) = encode_for_save(path, &content, enforced)?;
+    write_document(app, path, &content, &bytes, None, expected_hash)
+}
+
+/// Write `bytes`, the encoded `content`, to `path` unless it changed since the
+/// caller read it, backing up the old content first and bringing the watcher,
+/// index, history and Quick Open in step after.
+fn write_document(
+    app: &AppHandle,
+    path: &Path,
+    content: &str,
+    bytes: &[u8],
+    expected_modified: Option<u64>,
+    expected_hash: Option<&str>,
+) -> Result<SaveOutcome, CommandError> {
+    let created = !path.exists();
+    let outcome = save_if_unchanged(path, bytes, expected_modified, expected_hash, || {
+        back_up_before_save(app, path)
+    })?;
+
+    if matches!(outcome, SaveOutcome::Saved { .. }) {
+        record_own_write(&app.state::<FileWatchRegistry>(), path, bytes);
+        index_own_write(&app.state::<SearchIndexRegistry>(), path, content);
+        record_version(app, path, bytes);
+        if created {
+            invalidate_quick_open_containing(&app.state::<QuickOpenRegistry>(), path);
+        }
+    }
+
+    Ok(outcome)
 }
 
 /// Save markdown content with file picker (Save As)
 #[tauri::command]
-pub async fn save_markdown_file_as(app: AppHandle, content: String) -> Result<SaveResult, String> {
+pub async fn save_markdown_file_as(
+    app: AppHandle,
+    content: String,
+) -> Result<SaveResult, CommandError> {
     use tauri_plugin_dialog::DialogExt;
 
     // Open save dialog
@@ -183,102 +621,303 @@ pub async fn save_markdown_file_as(app: AppHandle, content: String) -> Result<Sa
                 path.set_extension("md");
             }
 
-            // Write content
-            atomic_write_file(&path, &content)?;
+            // Write content; replacing an existing file gets the same
+            // safeguards, backup and history entry as a save
+            check_not_emptying(&path, &content, false)?;
+            write_document(&app, &path, &content, content.as_bytes(), None, None)?;
+            grant_access(&app, &path)?;
 
             // Get file name
             let name = path
                 .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("Untitled.md")
-                .to_string();
-
-            // Convert path to string
-            let path_str = path
-                .to_str()
-                .ok_or("Invalid file path")?
-                .to_string();
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Untitled.md".to_string());
 
             Ok(SaveResult {
-                path: path_str,
+                path: IpcPath::from(&path),
                 name,
+                modified: read_modified_millis(&path),
+                hash: content_hash(content.as_bytes()),
             })
         }
-        None => Err("Save cancelled".to_string()),
+        None => Err(CommandError::Cancelled),
     }
 }
 
 /// Check if a file extension is markdown
-fn is_markdown_file(path: &Path) -> bool {
+pub(crate) fn is_markdown_file(path: &Path) -> bool {
     match path.extension().and_then(|e| e.to_str()) {
         Some(ext) => matches!(ext.to_lowercase().as_str(), "md" | "markdown" | "mdown" | "mkd"),
         None => false,
     }
 }
 
-/// Read directory entries recursively (with depth limit)
-fn read_dir_entries(path: &Path, depth: u32, max_depth: u32) -> Result<Vec<FileEntry>, String> {
+/// Every non-hidden, non-ignored file below `root`, without following symlinks.
+/// Unreadable directories are skipped.
+pub(crate) fn workspace_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    let mut rules = IgnoreRules::new(root, false);
+
+    while let Some(dir) = pending.pop() {
+        let Ok(dir_entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in dir_entries.flatten() {
+            let entry_path = entry.path();
+            if is_hidden(&entry_path) {
+                continue;
+            }
+
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+
+            if rules.listed(&entry_path, file_type.is_dir()).is_none() {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                pending.push(entry_path);
+            } else if file_type.is_file() {
+                files.push(entry_path);
+            }
+        }
+    }
+
+    files
+}
+
+/// Check if a path names a hidden (dot) file or directory
+pub(crate) fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+}
+
+/// Number of items a listing of `path` would show, or `None` when it cannot be read.
+fn count_listed_children(path: &Path, listing: &mut DirectoryListing) -> Option<usize> {
+    let count = fs::read_dir(path)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            let entry_path = entry.path();
+            let is_dir = entry_path.is_dir();
+            !is_hidden(&entry_path) && listing.rules.listed(&entry_path, is_dir).is_some()
+        })
+        .count();
+
+    Some(count)
+}
+
+/// Warning kind for an I/O error hit while listing.
+fn listing_warning_kind(error: &io::Error) -> &'static str {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => "permission_denied",
+        _ => "unreadable",
+    }
+}
+
+/// Build the tree entry for a single path, descending into directories up to `max_depth`.
+/// Ignored directories are never expanded eagerly. `metadata` describes the
+/// entry itself, or its target when it is a symbolic link.
+pub(crate) fn build_file_entry(
+    entry_path: &Path,
+    metadata: &fs::Metadata,
+    ignored: bool,
+    depth: u32,
+    max_depth: u32,
+    listing: &mut DirectoryListing,
+) -> FileEntry {
+    let name = entry_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let is_dir = metadata.is_dir();
+    let is_markdown = if is_dir { false } else { is_markdown_file(entry_path) };
+
+    let children = if is_dir && depth < max_depth && !ignored {
+        match read_dir_entries(entry_path, depth + 1, max_depth, listing) {
+            Ok(children) => Some(children),
+            Err(error) => {
+                listing.warn(entry_path, listing_warning_kind(&error), error);
+                Some(Vec::new())
+            }
+        }
+    } else if is_dir {
+        Some(Vec::new()) // Empty children, can be loaded lazily
+    } else {
+        None
+    };
+
+    let child_count = match &children {
+        Some(children) if !children.is_empty() => Some(children.len()),
+        Some(_) => count_listed_children(entry_path, listing),
+        None => None,
+    };
+
+    let symlink_target = if entry_path.is_symlink() {
+        fs::read_link(entry_path)
+            .ok()
+            .map(|target| target.to_string_lossy().into_owned())
+    } else {
+        None
+    };
+
+    FileEntry {
+        name,
+        path: IpcPath::from(entry_path),
+        is_dir,
+        is_markdown,
+        ignored,
+        size: if is_dir { 0 } else { metadata.len() },
+        created: metadata.created().ok().and_then(system_time_millis),
+        modified: metadata.modified().ok().and_then(system_time_millis),
+        readonly: metadata.permissions().readonly(),
+        symlink_target,
+        child_count,
+        children,
+    }
+}
+
+/// Order entries in place according to `sort`.
+fn sort_entries(entries: &mut [FileEntry], sort: &DirectorySort) {
+    entries.sort_by(|a, b| {
+        let folders = if sort.folders_first {
+            b.is_dir.cmp(&a.is_dir)
+        } else {
+            std::cmp::Ordering::Equal
+        };
+
+        let key = match sort.by {
+            SortKey::Name => std::cmp::Ordering::Equal,
+            SortKey::Modified => b.modified.cmp(&a.modified),
+            SortKey::Size => b.size.cmp(&a.size),
+        };
+
+        folders
+            .then(key)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+}
+
+/// Metadata for a listed entry, following symbolic links. Problem entries are
+/// reported as warnings and yield `None`.
+fn listed_metadata(
+    entry: &fs::DirEntry,
+    canonical_dir: Option<&Path>,
+    listing: &mut DirectoryListing,
+) -> Option<fs::Metadata> {
+    let entry_path = entry.path();
+
+    let file_type = match entry.file_type() {
+        Ok(file_type) => file_type,
+        Err(error) => {
+            listing.warn(&entry_path, listing_warning_kind(&error), error);
+            return None;
+        }
+    };
+
+    if !file_type.is_symlink() {
+        return match entry.metadata() {
+            Ok(metadata) => Some(metadata),
+            Err(error) => {
+                listing.warn(&entry_path, listing_warning_kind(&error), error);
+                None
+            }
+        };
+    }
+
+    let metadata = match fs::metadata(&entry_path) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            // A link that exists but cannot be resolved for any other reason is
+            // almost always a cycle of links (ELOOP).
+            let kind = match error.kind() {
+                io::ErrorKind::NotFound => "broken_symlink",
+                io::ErrorKind::PermissionDenied => "permission_denied",
+                _ => "symlink_loop",
+            };
+            listing.warn(&entry_path, kind, error);
+            return None;
+        }
+    };
+
+    if metadata.is_dir() {
+        let target = fs::canonicalize(&entry_path).ok();
+        if let (Some(target), Some(dir)) = (target, canonical_dir) {
+            if dir.starts_with(&target) {
+                listing.warn(
+                    &entry_path,
+                    "symlink_loop",
+                    "Symbolic link points to a folder that contains it",
+                );
+                return None;
+            }
+        }
+    }
+
+    Some(metadata)
+}
+
+/// Read directory entries recursively (with depth limit).
+///
+/// Only failing to read `path` itself is an error; problem entries inside it
+/// are skipped and recorded in the listing's warnings.
+fn read_dir_entries(
+    path: &Path,
+    depth: u32,
+    max_depth: u32,
+    listing: &mut DirectoryListing,
+) -> io::Result<Vec<FileEntry>> {
     let mut entries: Vec<FileEntry> = Vec::new();
     
-    let dir_entries = fs::read_dir(path)
-        .map_err(|e| format!("Failed to read directory: {}", e))?;
+    let dir_entries = fs::read_dir(path)?;
+    let canonical_dir = fs::canonicalize(path).ok();
     
     for entry in dir_entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                listing.warn(path, listing_warning_kind(&error), error);
+                continue;
+            }
+        };
         let entry_path = entry.path();
-        let metadata = entry.metadata()
-            .map_err(|e| format!("Failed to read metadata: {}", e))?;
-        
-        let name = entry_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("")
-            .to_string();
         
         // Skip hidden files and directories
-        if name.starts_with('.') {
+        if is_hidden(&entry_path) {
             continue;
         }
-        
-        let path_str = entry_path.to_str()
-            .ok_or("Invalid path encoding")?
-            .to_string();
-        
-        let is_dir = metadata.is_dir();
-        let is_markdown = if is_dir { false } else { is_markdown_file(&entry_path) };
-        
-        let children = if is_dir && depth < max_depth {
-            Some(read_dir_entries(&entry_path, depth + 1, max_depth)?)
-        } else if is_dir {
-            Some(Vec::new()) // Empty children, can be loaded lazily
-        } else {
-            None
+
+        let Some(metadata) = listed_metadata(&entry, canonical_dir.as_deref(), listing) else {
+            continue;
+        };
+
+        let Some(ignored) = listing.rules.listed(&entry_path, metadata.is_dir()) else {
+            continue;
         };
         
-        entries.push(FileEntry {
-            name,
-            path: path_str,
-            is_dir,
-            is_markdown,
-            children,
-        });
+        entries.push(build_file_entry(&entry_path, &metadata, ignored, depth, max_depth, listing));
     }
     
-    // Sort: directories first, then alphabetically
-    entries.sort_by(|a, b| {
-        match (a.is_dir, b.is_dir) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-        }
-    });
+    sort_entries(&mut entries, &listing.sort);
     
     Ok(entries)
 }
 
-/// Open a folder using folder picker
+/// Open a folder using folder picker.
+///
+/// Items matched by `.gitignore`, `.ignore`, `.keaignore` or git's own exclude
+/// files are left out unless `show_ignored` is set, in which case they are
+/// flagged as `ignored`. Without `show_ignored`, the workspace's saved setting applies.
 #[tauri::command]
-pub async fn open_folder_dialog(app: AppHandle) -> Result<FolderData, String> {
+pub async fn open_folder_dialog(
+    app: AppHandle,
+    show_ignored: Option<bool>,
+    sort: Option<DirectorySort>,
+) -> Result<FolderData, CommandError> {
     use tauri_plugin_dialog::DialogExt;
 
     let folder_path = app
@@ -292,369 +931,732 @@ pub async fn open_folder_dialog(app: AppHandle) -> Result<FolderData, String> {
                 .into_path()
                 .map_err(|_| "Invalid folder path")?;
 
+            grant_access(&app, &path)?;
+
             let name = path
                 .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("Folder")
-                .to_string();
-
-            let path_str = path
-                .to_str()
-                .ok_or("Invalid folder path")?
-                .to_string();
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Folder".to_string());
 
             // Read directory entries (2 levels deep initially)
-            let entries = read_dir_entries(&path, 0, 2)?;
+            let mut listing = DirectoryListing::new(
+                &path,
+                show_ignored.unwrap_or_else(|| settings_for(&app, &path).show_ignored),
+                sort.unwrap_or_default(),
+            );
+            let entries = read_dir_entries(&path, 0, 2, &mut listing)
+                .map_err(|e| CommandError::io(&path, "Failed to read directory", e))?;
 
             Ok(FolderData {
-                path: path_str,
+                path: IpcPath::from(&path),
                 name,
                 entries,
+                warnings: listing.warnings,
             })
         }
-        None => Err("No folder selected".to_string()),
+        None => Err(CommandError::Cancelled),
     }
 }
 
-/// Read directory contents (for lazy loading)
+/// Read directory contents (for lazy loading).
+///
+/// `root` is the workspace the directory belongs to, so ignore files in the
+/// folders above it apply; it defaults to the directory itself. `sort` picks
+/// the ordering, folders first by name unless given, and `show_ignored`
+/// defaults to the workspace's saved setting.
 #[tauri::command]
-pub async fn read_directory(path: String) -> Result<Vec<FileEntry>, String> {
-    let dir_path = Path::new(&path);
+pub async fn read_directory(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    path: IpcPath,
+    root: Option<IpcPath>,
+    show_ignored: Option<bool>,
+    sort: Option<DirectorySort>,
+) -> Result<DirectoryContents, CommandError> {
+    let dir_path = path.to_path_buf();
+    let dir_path = dir_path.as_path();
+    check_access(&access, dir_path)?;
     
     if !dir_path.exists() {
-        return Err("Directory does not exist".to_string());
+        return Err(CommandError::NotFound(path));
     }
     
     if !dir_path.is_dir() {
-        return Err("Path is not a directory".to_string());
+        return Err(CommandError::Other(format!("{} is not a directory", path.display())));
     }
     
-    read_dir_entries(dir_path, 0, 1)
+    let root = root.map_or_else(|| dir_path.to_path_buf(), |root| root.to_path_buf());
+    check_access(&access, &root)?;
+    let show_ignored = show_ignored.unwrap_or_else(|| settings_for(&app, &root).show_ignored);
+    let mut listing = DirectoryListing::new(&root, show_ignored, sort.unwrap_or_default());
+    let entries = read_dir_entries(dir_path, 0, 1, &mut listing)
+        .map_err(|e| CommandError::io(dir_path, "Failed to read directory", e))?;
+
+    Ok(DirectoryContents {
+        entries,
+        warnings: listing.warnings,
+    })
 }
 
 /// Read a file's content
 #[tauri::command]
-pub async fn read_file(path: String) -> Result<FileData, String> {
-    let file_path = Path::new(&path);
+pub async fn read_file(
+    access: State<'_, WorkspaceAccess>,
+    path: IpcPath,
+) -> Result<FileData, CommandError> {
+    let file_path = path.to_path_buf();
+    check_access(&access, &file_path)?;
     
     if !file_path.exists() {
-        return Err("File does not exist".to_string());
+        return Err(CommandError::NotFound(path));
     }
-    
-    let content = fs::read_to_string(file_path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
-    
-    let name = file_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("Untitled")
-        .to_string();
-    
-    Ok(FileData {
-        path,
-        content,
-        name,
-    })
+
+    read_document(&file_path)
+}
+
+/// Read an image linked from `document`, as raw bytes.
+#[tauri::command]
+pub async fn read_linked_image(
+    access: State<'_, WorkspaceAccess>,
+    document: IpcPath,
+    target: IpcPath,
+) -> Result<Response, CommandError> {
+    let target_path = target.to_path_buf();
+    check_linked_image(&access, &document.to_path_buf(), &target_path)?;
+
+    fs::read(&target_path)
+        .map(Response::new)
+        .map_err(|e| CommandError::io(&target_path, "Failed to read image", e))
 }
 
 /// Create a new file
 #[tauri::command]
-pub async fn create_file(path: String, content: Option<String>) -> Result<FileData, String> {
-    let file_path = Path::new(&path);
+pub async fn create_file(
+    access: State<'_, WorkspaceAccess>,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+    path: IpcPath,
+    content: Option<String>,
+) -> Result<FileData, CommandError> {
+    let file_path = path.to_path_buf();
+    let file_path = file_path.as_path();
+    check_access(&access, file_path)?;
     
     if file_path.exists() {
-        return Err("File already exists".to_string());
+        return Err(CommandError::AlreadyExists(path));
     }
     
     // Ensure parent directory exists
     if let Some(parent) = file_path.parent() {
         if !parent.exists() {
             fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+                .map_err(|e| CommandError::io(parent, "Failed to create parent directory", e))?;
         }
     }
     
     let file_content = content.unwrap_or_default();
 
     atomic_write_file(file_path, &file_content)?;
+
+    record_operation(
+        &journal,
+        FileOperation::Create {
+            path: path.clone(),
+            is_dir: false,
+            trash_id: None,
+            empty: false,
+        },
+    );
+    invalidate_quick_open_containing(&quick_open, file_path);
     
     let name = file_path
         .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("Untitled")
-        .to_string();
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Untitled".to_string());
     
+    let modified = read_modified_millis(file_path);
+    let hash = content_hash(file_content.as_bytes());
+    let lines = detect_line_style(&file_content);
+
     Ok(FileData {
         path,
         content: file_content,
         name,
+        modified,
+        hash,
+        encoding: TextFormat::UTF8.name().to_string(),
+        bom: false,
+        line_ending: lines.line_ending,
+        final_newline: lines.final_newline,
     })
 }
 
 /// Create a new folder
 #[tauri::command]
-pub async fn create_folder(path: String) -> Result<FileEntry, String> {
-    let folder_path = Path::new(&path);
+pub async fn create_folder(
+    access: State<'_, WorkspaceAccess>,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+    path: IpcPath,
+) -> Result<FileEntry, CommandError> {
+    let folder_path = path.to_path_buf();
+    let folder_path = folder_path.as_path();
+    check_access(&access, folder_path)?;
     
     if folder_path.exists() {
-        return Err("Folder already exists".to_string());
+        return Err(CommandError::AlreadyExists(path));
     }
     
     fs::create_dir_all(folder_path)
-        .map_err(|e| format!("Failed to create folder: {}", e))?;
-    
-    let name = folder_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("New Folder")
-        .to_string();
+        .map_err(|e| CommandError::io(folder_path, "Failed to create folder", e))?;
+
+    record_operation(
+        &journal,
+        FileOperation::Create {
+            path: path.clone(),
+            is_dir: true,
+            trash_id: None,
+            empty: false,
+        },
+    );
+    invalidate_quick_open_containing(&quick_open, folder_path);
     
-    Ok(FileEntry {
-        name,
-        path,
-        is_dir: true,
-        is_markdown: false,
-        children: Some(Vec::new()),
-    })
+    let metadata = fs::metadata(folder_path)
+        .map_err(|e| CommandError::io(folder_path, "Failed to read metadata", e))?;
+    let mut listing = DirectoryListing::new(folder_path, false, DirectorySort::default());
+
+    Ok(build_file_entry(folder_path, &metadata, false, 0, 0, &mut listing))
 }
 
-/// Rename a file or folder
+/// Rename a file or folder.
+///
+/// When `workspace_root` is given, relative links across the workspace's
+/// markdown files are rewritten to follow the item.
 #[tauri::command]
-pub async fn rename_item(old_path: String, new_name: String) -> Result<String, String> {
-    let old = Path::new(&old_path);
+pub async fn rename_item(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+    old_path: IpcPath,
+    new_name: String,
+    workspace_root: Option<IpcPath>,
+) -> Result<MoveResult, CommandError> {
+    let old = old_path.to_path_buf();
+    let old = old.as_path();
+    check_entry_access(&access, old)?;
+    if let Some(root) = &workspace_root {
+        check_access(&access, &root.to_path_buf())?;
+    }
     
     if !old.exists() {
-        return Err("Item does not exist".to_string());
+        return Err(CommandError::NotFound(old_path));
     }
     
     // Validate new name
     if new_name.is_empty() || new_name.contains('/') || new_name.contains('\\') {
-        return Err("Invalid name".to_string());
+        return Err(CommandError::InvalidName(new_name));
     }
     
     let new_path = old.parent()
-        .ok_or("Cannot get parent directory")?
+        .ok_or_else(|| CommandError::InvalidName(old_path.display().to_string()))?
         .join(&new_name);
+    check_entry_access(&access, &new_path)?;
     
     if new_path.exists() {
-        return Err("An item with that name already exists".to_string());
+        return Err(CommandError::AlreadyExists(IpcPath::from(new_path)));
     }
     
     fs::rename(old, &new_path)
-        .map_err(|e| format!("Failed to rename: {}", e))?;
-    
-    new_path.to_str()
-        .ok_or("Invalid path encoding".to_string())
-        .map(|s| s.to_string())
+        .map_err(|e| CommandError::io(old, "Failed to rename", e))?;
+    invalidate_quick_open_containing(&quick_open, old);
+    invalidate_quick_open_containing(&quick_open, &new_path);
+    move_history(&app, old, &new_path);
+
+    let link_edits = match workspace_root {
+        Some(root) => update_links_after_move(&app, &root.to_path_buf(), old, &new_path),
+        None => Vec::new(),
+    };
+    let updated_files = link_edits.iter().map(LinkEdit::update).collect();
+
+    let new_path = IpcPath::from(new_path);
+
+    record_operation(
+        &journal,
+        FileOperation::Move {
+            from: old_path,
+            to: new_path.clone(),
+            link_edits,
+        },
+    );
+
+    Ok(MoveResult {
+        path: new_path,
+        updated_files,
+    })
 }
 
-/// Delete a file or folder
-#[tauri::command]
-pub async fn delete_item(path: String) -> Result<(), String> {
-    let item_path = Path::new(&path);
-    
-    if !item_path.exists() {
-        return Err("Item does not exist".to_string());
+/// Where the trash records `item_path` as coming from: its own location, with
+/// only the folder resolved. A symlink is trashed itself, not its target.
+fn trashed_location(item_path: &Path) -> Result<PathBuf, CommandError> {
+    let parent = item_path.parent().ok_or("Invalid file path: missing parent directory")?;
+    let file_name = item_path.file_name().ok_or("Invalid file path")?;
+
+    let parent = fs::canonicalize(parent).map_err(|e| CommandError::io(parent, "Failed to resolve path", e))?;
+    Ok(parent.join(file_name))
+}
+
+/// Move a file or folder to the OS trash, or remove it outright when `permanent` is set
+pub(crate) fn delete_path(item_path: &Path, permanent: bool) -> Result<DeleteResult, CommandError> {
+    if fs::symlink_metadata(item_path).is_err() {
+        return Err(CommandError::NotFound(IpcPath::from(item_path)));
     }
-    
-    if item_path.is_dir() {
-        fs::remove_dir_all(item_path)
-            .map_err(|e| format!("Failed to delete folder: {}", e))?;
-    } else {
-        fs::remove_file(item_path)
-            .map_err(|e| format!("Failed to delete file: {}", e))?;
+
+    let path = IpcPath::from(item_path);
+
+    if permanent {
+        if item_path.is_dir() {
+            fs::remove_dir_all(item_path)
+                .map_err(|e| CommandError::io(item_path, "Failed to delete folder", e))?;
+        } else {
+            fs::remove_file(item_path)
+                .map_err(|e| CommandError::io(item_path, "Failed to delete file", e))?;
+        }
+
+        return Ok(DeleteResult {
+            path,
+            trashed: false,
+            trash_id: None,
+        });
     }
-    
-    Ok(())
+
+    let original_path = trashed_location(item_path)?;
+
+    trash::delete(item_path).map_err(|e| CommandError::Io {
+        path: Some(path.clone()),
+        message: format!("Failed to move item to trash: {}", e),
+        code: None,
+    })?;
+
+    Ok(DeleteResult {
+        path,
+        trashed: true,
+        trash_id: find_trash_id(&original_path),
+    })
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+fn find_trash_id(original_path: &Path) -> Option<String> {
+    trash::os_limited::list()
+        .ok()?
+        .into_iter()
+        .filter(|item| item.original_path() == original_path)
+        .max_by_key(|item| item.time_deleted)
+        .map(|item| item.id.to_string_lossy().into_owned())
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+fn find_trash_id(_original_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+fn trashed_original_path(trash_id: &str) -> Result<PathBuf, CommandError> {
+    trash::os_limited::list()
+        .map_err(|e| format!("Failed to read trash: {}", e))?
+        .into_iter()
+        .find(|item| item.id.to_string_lossy() == trash_id)
+        .map(|item| item.original_path())
+        .ok_or_else(|| "Item is no longer in the trash".into())
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+fn trashed_original_path(_trash_id: &str) -> Result<PathBuf, CommandError> {
+    Err(CommandError::Other(
+        "Restoring from the trash is not supported on this platform".to_string(),
+    ))
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+pub(crate) fn restore_from_trash(trash_id: &str) -> Result<IpcPath, CommandError> {
+    let item = trash::os_limited::list()
+        .map_err(|e| format!("Failed to read trash: {}", e))?
+        .into_iter()
+        .find(|item| item.id.to_string_lossy() == trash_id)
+        .ok_or("Item is no longer in the trash")?;
+
+    let restored_path = IpcPath::from(item.original_path());
+
+    trash::os_limited::restore_all([item]).map_err(|e| match e {
+        trash::Error::RestoreCollision { .. } => CommandError::Conflict(restored_path.clone()),
+        e => CommandError::Io {
+            path: Some(restored_path.clone()),
+            message: format!("Failed to restore item: {}", e),
+            code: None,
+        },
+    })?;
+
+    Ok(restored_path)
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+pub(crate) fn restore_from_trash(_trash_id: &str) -> Result<IpcPath, CommandError> {
+    Err(CommandError::Other(
+        "Restoring from the trash is not supported on this platform".to_string(),
+    ))
+}
+
+/// Delete a file or folder, moving it to the OS trash unless `permanent` is set
+#[tauri::command]
+pub async fn delete_item(
+    access: State<'_, WorkspaceAccess>,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+    path: IpcPath,
+    permanent: Option<bool>,
+) -> Result<DeleteResult, CommandError> {
+    let target = path.to_path_buf();
+    check_entry_access(&access, &target)?;
+    let result = delete_path(&target, permanent.unwrap_or(false))?;
+    invalidate_quick_open_containing(&quick_open, &target);
+
+    // Only deletes that can be restored are undoable.
+    if let Some(trash_id) = &result.trash_id {
+        record_operation(
+            &journal,
+            FileOperation::Delete {
+                path,
+                trash_id: trash_id.clone(),
+            },
+        );
+    }
+
+    Ok(result)
+}
+
+/// Restore an item previously moved to the trash by `delete_item`
+#[tauri::command]
+pub async fn restore_deleted_item(
+    access: State<'_, WorkspaceAccess>,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+    trash_id: String,
+) -> Result<IpcPath, CommandError> {
+    // The trash holds items from anywhere, so only those that go back into an
+    // opened workspace may be restored.
+    check_entry_access(&access, &trashed_original_path(&trash_id)?)?;
+
+    let restored = restore_from_trash(&trash_id)?;
+    invalidate_quick_open_containing(&quick_open, &restored.to_path_buf());
+    record_operation(
+        &journal,
+        FileOperation::Create {
+            is_dir: restored.to_path_buf().is_dir(),
+            path: restored.clone(),
+            trash_id: None,
+            empty: false,
+        },
+    );
+
+    Ok(restored)
 }
 
-/// Move a file or folder to a new location
+/// Move a file or folder to a new location.
+///
+/// When `workspace_root` is given, relative links across the workspace's
+/// markdown files are rewritten to follow the item.
 #[tauri::command]
-pub async fn move_item(source_path: String, target_dir: String) -> Result<String, String> {
-    let source = Path::new(&source_path);
-    let target_directory = Path::new(&target_dir);
+pub async fn move_item(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+    source_path: IpcPath,
+    target_dir: IpcPath,
+    workspace_root: Option<IpcPath>,
+) -> Result<MoveResult, CommandError> {
+    let source = source_path.to_path_buf();
+    let source = source.as_path();
+    let target_directory = target_dir.to_path_buf();
+    check_entry_access(&access, source)?;
+    check_access(&access, &target_directory)?;
+    if let Some(root) = &workspace_root {
+        check_access(&access, &root.to_path_buf())?;
+    }
     
     if !source.exists() {
-        return Err("Source does not exist".to_string());
+        return Err(CommandError::NotFound(source_path));
     }
     
     if !target_directory.is_dir() {
-        return Err("Target is not a directory".to_string());
+        return Err(CommandError::Other(format!("{} is not a directory", target_dir.display())));
     }
     
     let file_name = source.file_name()
-        .ok_or("Cannot get file name")?;
+        .ok_or_else(|| CommandError::InvalidName(source_path.display().to_string()))?;
     
     let new_path = target_directory.join(file_name);
     
     if new_path.exists() {
-        return Err("An item with that name already exists in the target location".to_string());
+        return Err(CommandError::AlreadyExists(IpcPath::from(new_path)));
     }
     
     fs::rename(source, &new_path)
-        .map_err(|e| format!("Failed to move: {}", e))?;
-    
-    new_path.to_str()
-        .ok_or("Invalid path encoding".to_string())
-        .map(|s| s.to_string())
+        .map_err(|e| CommandError::io(source, "Failed to move", e))?;
+    invalidate_quick_open_containing(&quick_open, source);
+    invalidate_quick_open_containing(&quick_open, &new_path);
+    move_history(&app, source, &new_path);
+
+    let link_edits = match workspace_root {
+        Some(root) => update_links_after_move(&app, &root.to_path_buf(), source, &new_path),
+        None => Vec::new(),
+    };
+    let updated_files = link_edits.iter().map(LinkEdit::update).collect();
+
+    let new_path = IpcPath::from(new_path);
+
+    record_operation(
+        &journal,
+        FileOperation::Move {
+            from: source_path,
+            to: new_path.clone(),
+            link_edits,
+        },
+    );
+
+    Ok(MoveResult {
+        path: new_path,
+        updated_files,
+    })
 }
 
-/// Start watching a file for external changes.
-#[tauri::command]
-pub async fn start_file_watch(
-    app: AppHandle,
-    state: State<'_, FileWatchRegistry>,
-    path: String,
-) -> Result<(), String> {
-    if path.is_empty() {
-        return Err("Path is required".to_string());
-    }
-
-    let file_path = Path::new(&path);
-    if !file_path.exists() {
-        return Err("File does not exist".to_string());
-    }
+#[cfg(test)]
+mod tests {
+    use super::{
+        atomic_write_file, content_hash, delete_path, encode_for_save, is_markdown_file,
+        read_dir_entries, read_modified_millis, save_if_unchanged, trashed_location, DirectoryListing,
+        DirectorySort, LineEnding, SaveFormat, SaveOutcome, SortKey,
+    };
+    use super::check_not_emptying;
+    use crate::commands::backup::back_up_into;
+    use crate::commands::error::CommandError;
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+    use std::path::PathBuf;
 
-    let stop_flag = {
-        let mut watchers = state
-            .watchers
-            .lock()
-            .map_err(|_| "Failed to lock watcher registry")?;
+    #[test]
+    fn atomic_write_file_creates_parents_and_replaces_existing_content() {
+        let root = make_temp_dir("atomic-write");
+        let file_path = root.join("nested").join("note.md");
 
-        if watchers.contains_key(&path) {
-            return Ok(());
-        }
+        atomic_write_file(&file_path, "first").expect("first write should succeed");
+        let first = fs::read_to_string(&file_path).expect("file should be readable after first write");
+        assert_eq!(first, "first");
 
-        let flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-        watchers.insert(path.clone(), flag.clone());
-        flag
-    };
+        atomic_write_file(&file_path, "second").expect("second write should succeed");
+        let second = fs::read_to_string(&file_path).expect("file should be readable after second write");
+        assert_eq!(second, "second");
 
-    let app_handle = app.clone();
-    let watched_path = path.clone();
+        let _ = fs::remove_dir_all(root);
+    }
 
-    thread::spawn(move || {
-        let mut last_exists = Path::new(&watched_path).exists();
-        let mut last_modified = if last_exists {
-            read_modified_time(Path::new(&watched_path))
-        } else {
-            None
-        };
+    #[cfg(unix)]
+    #[test]
+    fn atomic_write_file_keeps_permissions_and_extended_attributes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = make_temp_dir("atomic-write-attributes");
+        let file_path = root.join("shared.md");
+        fs::write(&file_path, "first").expect("failed to write initial file");
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o664)).expect("failed to set mode");
+        // Not every filesystem supports user attributes.
+        let has_xattr = xattr::set(&file_path, "user.kea.test", b"kept").is_ok();
+
+        atomic_write_file(&file_path, "second").expect("write should succeed");
+
+        let metadata = fs::metadata(&file_path).expect("file should exist");
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o664);
+        if has_xattr {
+            let value = xattr::get(&file_path, "user.kea.test").expect("attributes should be readable");
+            assert_eq!(value.as_deref(), Some(&b"kept"[..]));
+        }
+        assert_eq!(fs::read_to_string(&file_path).expect("file should be readable"), "second");
 
-        while !stop_flag.load(Ordering::Relaxed) {
-            let current_path = Path::new(&watched_path);
-            let exists = current_path.exists();
-
-            if !exists {
-                if last_exists {
-                    let _ = app_handle.emit(
-                        "file-watch-event",
-                        FileWatchEvent {
-                            path: watched_path.clone(),
-                            kind: "removed".to_string(),
-                        },
-                    );
-                }
-
-                last_exists = false;
-                last_modified = None;
-                thread::sleep(Duration::from_millis(400));
-                continue;
-            }
+        let _ = fs::remove_dir_all(root);
+    }
 
-            let modified = read_modified_time(current_path);
-            if last_exists {
-                if modified.is_some() && modified != last_modified {
-                    let _ = app_handle.emit(
-                        "file-watch-event",
-                        FileWatchEvent {
-                            path: watched_path.clone(),
-                            kind: "modified".to_string(),
-                        },
-                    );
-                }
+    #[test]
+    fn save_if_unchanged_reports_conflict_when_disk_content_changed() {
+        let root = make_temp_dir("save-conflict");
+        let file_path = root.join("note.md");
+        fs::write(&file_path, "original").expect("failed to write initial file");
+        let seen_hash = content_hash(b"original");
+
+        fs::write(&file_path, "external edit").expect("failed to simulate external edit");
+
+        let outcome = save_if_unchanged(&file_path, b"mine", None, Some(&seen_hash), || Ok(()))
+            .expect("save should not error");
+        match outcome {
+            SaveOutcome::Conflict { content, hash, .. } => {
+                assert_eq!(content, "external edit");
+                assert_eq!(hash, content_hash(b"external edit"));
             }
-
-            last_exists = true;
-            last_modified = modified;
-            thread::sleep(Duration::from_millis(400));
+            other => panic!("expected conflict, got {:?}", other),
         }
-    });
+        let on_disk = fs::read_to_string(&file_path).expect("file should be readable");
+        assert_eq!(on_disk, "external edit");
+
+        let current_hash = content_hash(b"external edit");
+        let outcome = save_if_unchanged(&file_path, b"mine", None, Some(&current_hash), || Ok(()))
+            .expect("save should not error");
+        assert_eq!(
+            outcome,
+            SaveOutcome::Saved {
+                modified: read_modified_millis(&file_path),
+                hash: content_hash(b"mine"),
+            }
+        );
+        let on_disk = fs::read_to_string(&file_path).expect("file should be readable");
+        assert_eq!(on_disk, "mine");
 
-    Ok(())
-}
+        let _ = fs::remove_dir_all(root);
+    }
 
-/// Stop watching a single file.
-#[tauri::command]
-pub async fn stop_file_watch(
-    state: State<'_, FileWatchRegistry>,
-    path: String,
-) -> Result<(), String> {
-    let removed = {
-        let mut watchers = state
-            .watchers
-            .lock()
-            .map_err(|_| "Failed to lock watcher registry")?;
-        watchers.remove(&path)
-    };
+    #[test]
+    fn save_if_unchanged_backs_up_only_saves_that_go_ahead() {
+        let root = make_temp_dir("save-backup-timing");
+        let file_path = root.join("note.md");
+        let backups = root.join("backups");
+        fs::write(&file_path, "original").expect("failed to write initial file");
+        let seen_hash = content_hash(b"original");
+        fs::write(&file_path, "external edit").expect("failed to simulate external edit");
+
+        let back_up = || back_up_into(&backups, &file_path, 10);
+        let outcome = save_if_unchanged(&file_path, b"mine", None, Some(&seen_hash), back_up)
+            .expect("save should not error");
+        assert!(matches!(outcome, SaveOutcome::Conflict { .. }));
+        assert!(!backups.exists(), "a refused save must not touch the backups");
+
+        let current_hash = content_hash(b"external edit");
+        let back_up = || back_up_into(&backups, &file_path, 10);
+        let outcome = save_if_unchanged(&file_path, b"mine", None, Some(&current_hash), back_up)
+            .expect("save should not error");
+        assert!(matches!(outcome, SaveOutcome::Saved { .. }));
+        let kept: Vec<String> = fs::read_dir(&backups)
+            .expect("backup directory should exist")
+            .flatten()
+            .map(|entry| fs::read_to_string(entry.path()).expect("backup should be readable"))
+            .collect();
+        assert_eq!(kept, ["external edit"]);
 
-    if let Some(flag) = removed {
-        flag.store(true, Ordering::Relaxed);
+        let _ = fs::remove_dir_all(root);
     }
 
-    Ok(())
-}
-
-/// Stop watching all files.
-#[tauri::command]
-pub async fn stop_all_file_watches(
-    state: State<'_, FileWatchRegistry>,
-) -> Result<(), String> {
-    let all_watchers = {
-        let mut watchers = state
-            .watchers
-            .lock()
-            .map_err(|_| "Failed to lock watcher registry")?;
-        watchers.drain().map(|(_, flag)| flag).collect::<Vec<_>>()
-    };
+    #[test]
+    fn check_not_emptying_refuses_to_blank_a_file_unless_forced() {
+        let root = make_temp_dir("save-empty");
+        let file_path = root.join("note.md");
+        fs::write(&file_path, "# Notes\n").expect("failed to write initial file");
+
+        assert_eq!(
+            check_not_emptying(&file_path, "", false),
+            Err(CommandError::EmptyOverwrite(file_path.as_path().into()))
+        );
+        assert!(check_not_emptying(&file_path, "", true).is_ok());
+        assert!(check_not_emptying(&file_path, "# Notes\n", false).is_ok());
+
+        fs::write(&file_path, b"\xEF\xBB\xBF").expect("failed to write empty file");
+        assert!(check_not_emptying(&file_path, "", false).is_ok());
+        assert!(check_not_emptying(&root.join("new.md"), "", false).is_ok());
 
-    for flag in all_watchers {
-        flag.store(true, Ordering::Relaxed);
+        let _ = fs::remove_dir_all(root);
     }
 
-    Ok(())
-}
+    #[test]
+    fn encode_for_save_keeps_the_line_style_on_disk_unless_overridden() {
+        let root = make_temp_dir("save-line-style");
+        let file_path = root.join("windows.md");
+        fs::write(&file_path, "one\r\ntwo").expect("failed to write initial file");
+
+        let (content, bytes) = encode_for_save(&file_path, "one\ntwo\nthree\n", SaveFormat::default())
+            .expect("content should encode");
+        assert_eq!(content, "one\r\ntwo\r\nthree");
+        assert_eq!(bytes, b"one\r\ntwo\r\nthree");
+
+        fs::write(&file_path, "one\r\ntwo\nthree\r\n").expect("failed to write mixed file");
+        let (content, _) = encode_for_save(&file_path, "one\ntwo\nthree\nfour\n", SaveFormat::default())
+            .expect("content should encode");
+        assert_eq!(content, "one\r\ntwo\nthree\r\nfour\r\n");
+
+        let policy = SaveFormat {
+            line_ending: Some(LineEnding::Lf),
+            final_newline: Some(true),
+            ..SaveFormat::default()
+        };
+        let (content, _) = encode_for_save(&file_path, "one\r\ntwo", policy).expect("content should encode");
+        assert_eq!(content, "one\ntwo\n");
 
-#[cfg(test)]
-mod tests {
-    use super::{atomic_write_file, is_markdown_file, read_dir_entries};
-    use std::fs;
-    use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
+        let (content, _) = encode_for_save(&root.join("new.md"), "a\r\nb\r\n", SaveFormat::default())
+            .expect("content should encode");
+        assert_eq!(content, "a\r\nb\r\n");
+
+        let _ = fs::remove_dir_all(root);
+    }
 
-    fn make_temp_dir(test_name: &str) -> PathBuf {
-        let unique = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system time should be after unix epoch")
-            .as_nanos();
+    #[cfg(unix)]
+    #[test]
+    fn trashed_location_keeps_symlinks_unresolved() {
+        let root = make_temp_dir("trash-symlink");
+        fs::create_dir_all(root.join("real")).expect("failed to create folder");
+        fs::write(root.join("real").join("target.md"), "target").expect("failed to write target");
+        std::os::unix::fs::symlink(root.join("real"), root.join("alias")).expect("failed to link folder");
+        std::os::unix::fs::symlink(root.join("real").join("target.md"), root.join("link.md"))
+            .expect("failed to link file");
+
+        let resolved_root = fs::canonicalize(&root).expect("root should resolve");
+        assert_eq!(
+            trashed_location(&root.join("link.md")).expect("link should resolve"),
+            resolved_root.join("link.md")
+        );
+        assert_eq!(
+            trashed_location(&root.join("alias").join("target.md")).expect("file should resolve"),
+            resolved_root.join("real").join("target.md")
+        );
 
-        let dir = std::env::temp_dir().join(format!("kea-tests-{}-{}", test_name, unique));
-        fs::create_dir_all(&dir).expect("failed to create temporary test directory");
-        dir
+        let _ = fs::remove_dir_all(root);
     }
 
     #[test]
-    fn atomic_write_file_creates_parents_and_replaces_existing_content() {
-        let root = make_temp_dir("atomic-write");
-        let file_path = root.join("nested").join("note.md");
+    fn delete_path_permanently_removes_folders_when_requested() {
+        let root = make_temp_dir("delete-permanent");
+        let folder = root.join("notes");
+        fs::create_dir_all(&folder).expect("failed to create folder");
+        fs::write(folder.join("note.md"), "note").expect("failed to write file");
 
-        atomic_write_file(&file_path, "first").expect("first write should succeed");
-        let first = fs::read_to_string(&file_path).expect("file should be readable after first write");
-        assert_eq!(first, "first");
+        let result = delete_path(&folder, true).expect("permanent delete should succeed");
 
-        atomic_write_file(&file_path, "second").expect("second write should succeed");
-        let second = fs::read_to_string(&file_path).expect("file should be readable after second write");
-        assert_eq!(second, "second");
+        assert!(!result.trashed);
+        assert!(result.trash_id.is_none());
+        assert!(!folder.exists());
+        assert!(delete_path(&folder, true).is_err());
 
         let _ = fs::remove_dir_all(root);
     }
@@ -678,7 +1680,13 @@ mod tests {
         fs::write(root.join("a.txt"), "plain").expect("failed to write text file");
         fs::write(root.join(".hidden.md"), "hidden").expect("failed to write hidden file");
 
-        let entries = read_dir_entries(&root, 0, 1).expect("read_dir_entries should succeed");
+        let entries = read_dir_entries(
+            &root,
+            0,
+            1,
+            &mut DirectoryListing::new(&root, false, DirectorySort::default()),
+        )
+        .expect("read_dir_entries should succeed");
         let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
 
         assert_eq!(names, vec!["a-folder", "b-folder", "a.txt", "z.md"]);
@@ -699,11 +1707,173 @@ mod tests {
         fs::create_dir_all(root.join("folder")).expect("failed to create folder");
         fs::write(root.join("folder").join("child.md"), "child").expect("failed to write child file");
 
-        let entries = read_dir_entries(&root, 0, 0).expect("read_dir_entries should succeed");
+        let entries = read_dir_entries(
+            &root,
+            0,
+            0,
+            &mut DirectoryListing::new(&root, false, DirectorySort::default()),
+        )
+        .expect("read_dir_entries should succeed");
         let folder = entries.iter().find(|entry| entry.name == "folder").expect("folder should exist");
 
         assert!(folder.is_dir);
         assert_eq!(folder.children.as_ref().map(|children| children.len()), Some(0));
+        assert_eq!(folder.child_count, Some(1));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn read_dir_entries_reports_metadata_and_honours_sort_options() {
+        let root = make_temp_dir("read-dir-metadata");
+        fs::create_dir_all(root.join("folder")).expect("failed to create folder");
+        fs::write(root.join("big.md"), "a much longer note").expect("failed to write big file");
+        fs::write(root.join("small.md"), "short").expect("failed to write small file");
+
+        let by_size = read_dir_entries(
+            &root,
+            0,
+            0,
+            &mut DirectoryListing::new(
+                &root,
+                false,
+                DirectorySort {
+                    by: SortKey::Size,
+                    folders_first: false,
+                },
+            ),
+        )
+        .expect("read_dir_entries should succeed");
+        let names: Vec<&str> = by_size.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["big.md", "small.md", "folder"]);
+
+        let small = &by_size[1];
+        assert_eq!(small.size, 5);
+        assert!(small.modified.is_some());
+        assert!(!small.readonly);
+        assert_eq!(small.symlink_target, None);
+        assert_eq!(small.child_count, None);
+
+        let by_name = read_dir_entries(
+            &root,
+            0,
+            0,
+            &mut DirectoryListing::new(&root, false, DirectorySort::default()),
+        )
+        .expect("read_dir_entries should succeed");
+        let names: Vec<&str> = by_name.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["folder", "big.md", "small.md"]);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn read_dir_entries_hides_or_flags_ignored_items() {
+        let root = make_temp_dir("read-dir-ignored");
+        fs::create_dir_all(root.join("node_modules").join("pkg")).expect("failed to create ignored folder");
+        fs::write(root.join(".gitignore"), "node_modules/\n").expect("failed to write gitignore");
+        fs::write(root.join("note.md"), "note").expect("failed to write note");
+
+        let hidden = read_dir_entries(
+            &root,
+            0,
+            2,
+            &mut DirectoryListing::new(&root, false, DirectorySort::default()),
+        )
+        .expect("read_dir_entries should succeed");
+        let names: Vec<&str> = hidden.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["note.md"]);
+
+        let shown = read_dir_entries(
+            &root,
+            0,
+            2,
+            &mut DirectoryListing::new(&root, true, DirectorySort::default()),
+        )
+        .expect("read_dir_entries should succeed");
+        let ignored = shown.iter().find(|entry| entry.name == "node_modules").expect("ignored folder should be listed");
+        assert!(ignored.ignored);
+        assert_eq!(ignored.children.as_ref().map(|children| children.len()), Some(0));
+        assert!(!shown.iter().any(|entry| entry.name == "note.md" && entry.ignored));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_dir_entries_skips_and_reports_broken_and_looping_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = make_temp_dir("read-dir-symlinks");
+        fs::create_dir_all(root.join("notes")).expect("failed to create folder");
+        fs::write(root.join("notes").join("a.md"), "a").expect("failed to write note");
+        symlink(root.join("missing.md"), root.join("broken.md")).expect("failed to create broken link");
+        symlink(&root, root.join("notes").join("up")).expect("failed to create parent link");
+        symlink(root.join("cycle-b"), root.join("cycle-a")).expect("failed to create cycle link");
+        symlink(root.join("cycle-a"), root.join("cycle-b")).expect("failed to create cycle link");
+        symlink(root.join("notes"), root.join("linked")).expect("failed to create folder link");
+
+        let mut listing = DirectoryListing::new(&root, false, DirectorySort::default());
+        let entries = read_dir_entries(&root, 0, 2, &mut listing).expect("listing should not fail");
+
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["linked", "notes"]);
+        let linked = &entries[0];
+        assert!(linked.is_dir);
+        assert!(linked.symlink_target.is_some());
+        assert_eq!(linked.child_count, Some(1));
+
+        let mut kinds: Vec<(String, &str)> = listing
+            .warnings
+            .iter()
+            .map(|warning| {
+                let name = warning.path
+                    .to_path_buf()
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                (name, warning.kind.as_str())
+            })
+            .collect();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            vec![
+                ("broken.md".to_string(), "broken_symlink"),
+                ("cycle-a".to_string(), "symlink_loop"),
+                ("cycle-b".to_string(), "symlink_loop"),
+                ("up".to_string(), "symlink_loop"),
+                ("up".to_string(), "symlink_loop"),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_dir_entries_lists_non_utf8_names_losslessly() {
+        use super::IpcPath;
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let root = make_temp_dir("read-dir-non-utf8");
+        let file_path = root.join(OsString::from_vec(b"caf\xe9.md".to_vec()));
+        if fs::write(&file_path, "bytes").is_err() {
+            // Some filesystems only accept UTF-8 names.
+            let _ = fs::remove_dir_all(root);
+            return;
+        }
+
+        let mut listing = DirectoryListing::new(&root, false, DirectorySort::default());
+        let entries = read_dir_entries(&root, 0, 1, &mut listing).expect("listing should succeed");
+
+        assert!(listing.warnings.is_empty());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "caf\u{fffd}.md");
+        assert!(entries[0].is_markdown);
+        assert!(matches!(entries[0].path, IpcPath::Raw { .. }));
+        assert_eq!(entries[0].path.to_path_buf(), file_path);
 
         let _ = fs::remove_dir_all(root);
     }
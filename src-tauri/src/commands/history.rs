@@ -0,0 +1,542 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+use super::encoding::decode_text;
+use super::error::CommandError;
+use super::file::{atomic_write_file, content_hash, read_document, restore_document, FileData, SaveOutcome};
+use super::paths::IpcPath;
+use super::sandbox::workspace_of;
+use super::watch::{line_diff, DiffHunk};
+use crate::{HistoryRegistry, WorkspaceAccess};
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// Every version saved within this window is kept.
+const KEEP_EVERY_VERSION_FOR: u64 = DAY_MILLIS;
+
+/// Older versions are thinned to the last one of each day, and dropped after this.
+const MAX_VERSION_AGE: u64 = 90 * DAY_MILLIS;
+
+/// Upper bound on versions kept per file, whatever their age.
+const MAX_VERSIONS_PER_FILE: usize = 200;
+
+/// Present while pruning may have left contents no version refers to.
+const PRUNED_MARKER: &str = "pruned";
+
+/// One saved version of a file. `hash` identifies its content in the store.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FileVersion {
+    pub hash: String,
+    /// When the version was saved, in milliseconds since the Unix epoch.
+    pub saved: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileVersionContent {
+    pub hash: String,
+    pub content: String,
+    pub encoding: String,
+}
+
+/// Version history of one workspace.
+///
+/// Contents are stored once each, gzip-compressed and named by their SHA-256,
+/// under `objects/`. Each file's versions are appended to its own log at
+/// `timelines/<path relative to the workspace>/versions.jsonl`, so a save adds
+/// one line and renaming a file or folder renames its logs along with it.
+pub struct HistoryStore {
+    dir: PathBuf,
+    timelines: HashMap<PathBuf, Vec<FileVersion>>,
+}
+
+/// The versions in a log, oldest first. A line cut short by a crash is skipped.
+fn read_log(log: &Path) -> Vec<FileVersion> {
+    fs::read_to_string(log)
+        .map(|json| {
+            json.lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn log_line(version: &FileVersion) -> Result<String, CommandError> {
+    serde_json::to_string(version)
+        .map(|json| json + "\n")
+        .map_err(|e| format!("Failed to serialize version history: {}", e).into())
+}
+
+fn append_to_log(log: &Path, version: &FileVersion) -> Result<(), CommandError> {
+    if let Some(parent) = log.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| CommandError::io(parent, "Failed to create history directory", e))?;
+    }
+
+    let line = log_line(version)?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| CommandError::io(log, "Failed to record version", e))
+}
+
+/// Add the hashes in every log under `dir` to `hashes`.
+fn collect_logged_hashes(dir: &Path, hashes: &mut HashSet<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_logged_hashes(&path, hashes);
+        } else {
+            hashes.extend(read_log(&path).into_iter().map(|version| version.hash));
+        }
+    }
+}
+
+impl HistoryStore {
+    fn open(dir: PathBuf) -> Self {
+        // Contents dropped by pruning are deleted once, when the store is next
+        // opened, so that a save never has to read the logs of every file.
+        if dir.join(PRUNED_MARKER).exists() {
+            Self::collect_garbage(&dir);
+        }
+
+        Self {
+            dir,
+            timelines: HashMap::new(),
+        }
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("objects").join(format!("{}.gz", hash))
+    }
+
+    fn timeline_dir(&self, key: &Path) -> PathBuf {
+        self.dir.join("timelines").join(key)
+    }
+
+    fn log_path(&self, key: &Path) -> PathBuf {
+        self.timeline_dir(key).join("versions.jsonl")
+    }
+
+    fn versions(&mut self, key: &Path) -> &[FileVersion] {
+        let log = self.log_path(key);
+        self.timelines
+            .entry(key.to_path_buf())
+            .or_insert_with(|| read_log(&log))
+    }
+
+    /// Add `bytes` as the newest version of `key`, unless it is already the newest.
+    fn record(&mut self, key: &Path, bytes: &[u8], saved: u64) -> Result<(), CommandError> {
+        let hash = content_hash(bytes);
+        if self
+            .versions(key)
+            .last()
+            .is_some_and(|latest| latest.hash == hash)
+        {
+            return Ok(());
+        }
+
+        let object = self.object_path(&hash);
+        if !object.exists() {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(bytes)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| CommandError::io(&object, "Failed to compress version", e))
+                .and_then(|compressed| atomic_write_file(&object, compressed))?;
+        }
+
+        let version = FileVersion {
+            hash,
+            saved,
+            size: bytes.len() as u64,
+        };
+        let log = self.log_path(key);
+        let versions = self.timelines.entry(key.to_path_buf()).or_default();
+        versions.push(version.clone());
+
+        if !prune_versions(versions, saved) {
+            return append_to_log(&log, &version);
+        }
+
+        let lines = versions.iter().map(log_line).collect::<Result<String, _>>()?;
+        atomic_write_file(&log, lines)?;
+        atomic_write_file(&self.dir.join(PRUNED_MARKER), "")
+    }
+
+    /// The content of version `hash` of `key`, or `None` when there is no such version.
+    fn read(&mut self, key: &Path, hash: &str) -> Result<Option<Vec<u8>>, CommandError> {
+        if !self.versions(key).iter().any(|version| version.hash == hash) {
+            return Ok(None);
+        }
+
+        let object = self.object_path(hash);
+        let compressed = match fs::read(&object) {
+            Ok(compressed) => compressed,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CommandError::io(&object, "Failed to read version", e)),
+        };
+
+        let mut bytes = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut bytes)
+            .map_err(|e| CommandError::io(&object, "Failed to decompress version", e))?;
+
+        if content_hash(&bytes) != hash {
+            return Err(CommandError::Other(format!("Version {} is corrupted", hash)));
+        }
+
+        Ok(Some(bytes))
+    }
+
+    /// Carry the history of `from`, a file or a folder, over to `to` after it
+    /// was renamed or moved. History left at `to` by an earlier item is replaced.
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<(), CommandError> {
+        let source = self.timeline_dir(from);
+        if !source.exists() {
+            return Ok(());
+        }
+
+        let target = self.timeline_dir(to);
+        if target.exists() {
+            fs::remove_dir_all(&target)
+                .map_err(|e| CommandError::io(&target, "Failed to replace history", e))?;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| CommandError::io(parent, "Failed to create history directory", e))?;
+        }
+        fs::rename(&source, &target)
+            .map_err(|e| CommandError::io(&source, "Failed to move history", e))?;
+
+        // Cached timelines below either path no longer match their logs.
+        self.timelines.clear();
+        Ok(())
+    }
+
+    /// Delete stored contents no version refers to any more.
+    fn collect_garbage(dir: &Path) {
+        let mut referenced = HashSet::new();
+        collect_logged_hashes(&dir.join("timelines"), &mut referenced);
+
+        let Ok(objects) = fs::read_dir(dir.join("objects")) else {
+            return;
+        };
+
+        for entry in objects.flatten() {
+            let path = entry.path();
+            let unreferenced = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|hash| !referenced.contains(hash));
+
+            if unreferenced {
+                let _ = fs::remove_file(path);
+            }
+        }
+
+        let _ = fs::remove_file(dir.join(PRUNED_MARKER));
+    }
+}
+
+/// Apply the retention policy to `versions`, oldest first, as of `now`.
+/// Returns whether any version was dropped.
+fn prune_versions(versions: &mut Vec<FileVersion>, now: u64) -> bool {
+    let before = versions.len();
+    let mut kept: Vec<FileVersion> = Vec::with_capacity(before);
+
+    for version in versions.drain(..) {
+        let age = now.saturating_sub(version.saved);
+        if age > MAX_VERSION_AGE {
+            continue;
+        }
+
+        // Past the first day, a later version from the same day replaces an earlier one.
+        if let Some(previous) = kept.last() {
+            let previous_age = now.saturating_sub(previous.saved);
+            if previous_age > KEEP_EVERY_VERSION_FOR
+                && age > KEEP_EVERY_VERSION_FOR
+                && previous.saved / DAY_MILLIS == version.saved / DAY_MILLIS
+            {
+                kept.pop();
+            }
+        }
+
+        kept.push(version);
+    }
+
+    let excess = kept.len().saturating_sub(MAX_VERSIONS_PER_FILE);
+    kept.drain(..excess);
+
+    *versions = kept;
+    versions.len() != before
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+fn history_dir(app: &AppHandle, root: &Path) -> Result<PathBuf, CommandError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+
+    Ok(data_dir
+        .join("history")
+        .join(content_hash(root.as_os_str().as_encoded_bytes())))
+}
+
+/// Run `action` on the history store of the workspace at `root`.
+fn with_root_store<T>(
+    app: &AppHandle,
+    root: PathBuf,
+    action: impl FnOnce(&mut HistoryStore) -> Result<T, CommandError>,
+) -> Result<T, CommandError> {
+    let registry = app.state::<HistoryRegistry>();
+    let mut stores = registry
+        .stores
+        .lock()
+        .map_err(|_| "Failed to lock version history")?;
+
+    let store = match stores.get_mut(&root) {
+        Some(store) => store,
+        None => {
+            let store = HistoryStore::open(history_dir(app, &root)?);
+            stores.entry(root).or_insert(store)
+        }
+    };
+
+    action(store)
+}
+
+/// Run `action` on the history store of the workspace containing `path`, with
+/// the file's key in that store: its path relative to the workspace, as is, so
+/// names that are not valid UTF-8 get history too.
+fn with_store<T>(
+    app: &AppHandle,
+    path: &Path,
+    action: impl FnOnce(&mut HistoryStore, &Path) -> Result<T, CommandError>,
+) -> Result<T, CommandError> {
+    let (root, relative) = workspace_of(&app.state::<WorkspaceAccess>(), path)?;
+    with_root_store(app, root, |store| action(store, &relative))
+}
+
+/// Record the content just saved to `path` in its workspace's history.
+/// History is best effort: failures never fail the save.
+pub(crate) fn record_version(app: &AppHandle, path: &Path, bytes: &[u8]) {
+    let _ = with_store(app, path, |store, key| store.record(key, bytes, now_millis()));
+}
+
+/// Keep the history of a file, or of every file in a folder, after it moved
+/// from `from` to `to` within its workspace. Best effort, like recording.
+pub(crate) fn move_history(app: &AppHandle, from: &Path, to: &Path) {
+    let access = app.state::<WorkspaceAccess>();
+    let (Ok((root, from_relative)), Ok((to_root, to_relative))) =
+        (workspace_of(&access, from), workspace_of(&access, to))
+    else {
+        return;
+    };
+    if root != to_root {
+        return;
+    }
+
+    let _ = with_root_store(app, root, |store| {
+        store.rename(&from_relative, &to_relative)
+    });
+}
+
+/// Version `hash` of the file at `path`, which is reported as not found when
+/// it has no such version.
+fn read_version(store: &mut HistoryStore, key: &Path, hash: &str, path: &Path) -> Result<Vec<u8>, CommandError> {
+    store
+        .read(key, hash)?
+        .ok_or_else(|| CommandError::NotFound(IpcPath::from(path)))
+}
+
+/// List the saved versions of a file, newest first.
+#[tauri::command]
+pub async fn list_file_history(app: AppHandle, path: IpcPath) -> Result<Vec<FileVersion>, CommandError> {
+    with_store(&app, &path.to_path_buf(), |store, key| {
+        Ok(store.versions(key).iter().rev().cloned().collect())
+    })
+}
+
+/// Fetch the content of one saved version of a file.
+#[tauri::command]
+pub async fn read_file_version(
+    app: AppHandle,
+    path: IpcPath,
+    hash: String,
+) -> Result<FileVersionContent, CommandError> {
+    let file_path = path.to_path_buf();
+    let bytes = with_store(&app, &file_path, |store, key| read_version(store, key, &hash, &file_path))?;
+    let (content, format) = decode_text(&bytes);
+
+    Ok(FileVersionContent {
+        hash,
+        content,
+        encoding: format.name().to_string(),
+    })
+}
+
+/// Line diff from version `from` to version `to` of a file, or to the file on
+/// disk when `to` is not given.
+#[tauri::command]
+pub async fn diff_file_versions(
+    app: AppHandle,
+    path: IpcPath,
+    from: String,
+    to: Option<String>,
+) -> Result<Vec<DiffHunk>, CommandError> {
+    let file_path = path.to_path_buf();
+    let (old, new) = with_store(&app, &file_path, |store, key| {
+        let old = read_version(store, key, &from, &file_path)?;
+        let new = match &to {
+            Some(to) => read_version(store, key, to, &file_path)?,
+            None => fs::read(&file_path)
+                .map_err(|e| CommandError::io(&file_path, "Failed to read file", e))?,
+        };
+        Ok((old, new))
+    })?;
+
+    Ok(line_diff(&decode_text(&old).0, &decode_text(&new).0))
+}
+
+/// Put a saved version back in place of the file, returning the restored file.
+/// It is saved like an editor save, so the restore itself becomes the newest
+/// version and a file changed while restoring is reported as a conflict.
+#[tauri::command]
+pub async fn restore_file_version(
+    app: AppHandle,
+    path: IpcPath,
+    hash: String,
+) -> Result<FileData, CommandError> {
+    let file_path = path.to_path_buf();
+    let bytes = with_store(&app, &file_path, |store, key| read_version(store, key, &hash, &file_path))?;
+    let current = fs::read(&file_path).ok().map(|current| content_hash(&current));
+
+    match restore_document(&app, &file_path, &bytes, current.as_deref())? {
+        SaveOutcome::Saved { .. } => read_document(&file_path),
+        SaveOutcome::Conflict { .. } => Err(CommandError::Conflict(path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prune_versions, FileVersion, HistoryStore, DAY_MILLIS};
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+    use std::path::Path;
+
+    fn version(hash: &str, saved: u64) -> FileVersion {
+        FileVersion {
+            hash: hash.to_string(),
+            saved,
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn history_store_deduplicates_versions_and_survives_reopening() {
+        let dir = make_temp_dir("history-store");
+        let mut store = HistoryStore::open(dir.clone());
+
+        store.record(Path::new("notes/a.md"), b"# One\n", 1_000).expect("version should be recorded");
+        store.record(Path::new("notes/a.md"), b"# One\n", 2_000).expect("duplicate should be skipped");
+        store.record(Path::new("notes/a.md"), b"# Two\n", 3_000).expect("version should be recorded");
+        store.record(Path::new("b.md"), b"# One\n", 4_000).expect("version should be recorded");
+
+        let versions = store.versions(Path::new("notes/a.md")).to_vec();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(fs::read_dir(dir.join("objects")).expect("objects should exist").count(), 2);
+
+        let mut reopened = HistoryStore::open(dir.clone());
+        assert_eq!(reopened.versions(Path::new("notes/a.md")), versions.as_slice());
+        assert_eq!(
+            reopened.read(Path::new("notes/a.md"), &versions[0].hash).expect("version should be readable"),
+            Some(b"# One\n".to_vec())
+        );
+        assert_eq!(reopened.read(Path::new("b.md"), &versions[1].hash).expect("lookup should succeed"), None);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn history_store_appends_to_per_file_logs_and_follows_renames() {
+        let dir = make_temp_dir("history-rename");
+        let mut store = HistoryStore::open(dir.clone());
+
+        store.record(Path::new("notes/a.md"), b"one", 1_000).expect("version should be recorded");
+        store.record(Path::new("notes/a.md"), b"two", 2_000).expect("version should be recorded");
+        let log = dir.join("timelines").join("notes").join("a.md").join("versions.jsonl");
+        let lines = fs::read_to_string(&log).expect("log should exist");
+        assert_eq!(lines.lines().count(), 2);
+
+        store.rename(Path::new("notes"), Path::new("archive")).expect("folder history should move");
+        assert!(store.versions(Path::new("notes/a.md")).is_empty());
+        assert_eq!(store.versions(Path::new("archive/a.md")).len(), 2);
+
+        store.rename(Path::new("archive/a.md"), Path::new("archive/b.md")).expect("file history should move");
+        let mut reopened = HistoryStore::open(dir.clone());
+        assert_eq!(reopened.versions(Path::new("archive/b.md")).len(), 2);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn history_store_keeps_files_whose_names_are_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = make_temp_dir("history-bytes");
+        let key = Path::new("notes").join(OsStr::from_bytes(b"caf\xe9.md"));
+        let mut store = HistoryStore::open(dir.clone());
+        store.record(&key, b"one", 1_000).expect("version should be recorded");
+
+        let mut reopened = HistoryStore::open(dir.clone());
+        let versions = reopened.versions(&key).to_vec();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(
+            reopened.read(&key, &versions[0].hash).expect("version should be readable"),
+            Some(b"one".to_vec())
+        );
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn prune_versions_thins_old_days_and_drops_expired_versions() {
+        let now = 200 * DAY_MILLIS + DAY_MILLIS / 2;
+        let mut versions = vec![
+            version("expired", now - 100 * DAY_MILLIS),
+            version("morning", now - 10 * DAY_MILLIS - 3 * 3_600_000),
+            version("evening", now - 10 * DAY_MILLIS + 3 * 3_600_000),
+            version("yesterday", now - 2 * DAY_MILLIS),
+            version("recent-1", now - 3_600_000),
+            version("recent-2", now - 60_000),
+        ];
+
+        assert!(prune_versions(&mut versions, now));
+
+        let hashes: Vec<&str> = versions.iter().map(|version| version.hash.as_str()).collect();
+        assert_eq!(hashes, ["evening", "yesterday", "recent-1", "recent-2"]);
+        assert!(!prune_versions(&mut versions, now));
+    }
+}
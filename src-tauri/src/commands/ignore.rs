@@ -0,0 +1,236 @@
+use ::ignore::gitignore::{gitconfig_excludes_path, Gitignore, GitignoreBuilder};
+use ::ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Ignore files read from every workspace directory, lowest precedence first.
+/// `.keaignore` hides items from Kea without affecting git.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".keaignore"];
+
+/// Build a matcher from ignore files whose patterns are relative to `root`,
+/// skipping missing files and lines that are not valid globs.
+fn build_matcher(root: &Path, files: impl IntoIterator<Item = PathBuf>) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(root);
+    for file in files {
+        if file.is_file() {
+            // Invalid lines are reported but the rest of the file still applies.
+            let _ = builder.add(file);
+        }
+    }
+
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+/// The root of the git repository containing `root`, or `root` itself when it
+/// is in none.
+fn repository_root(root: &Path) -> PathBuf {
+    root.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(root)
+        .to_path_buf()
+}
+
+/// Git's own exclude files for a repository: the user's `core.excludesFile` and
+/// `.git/info/exclude`, lowest precedence first. Both are relative to the
+/// repository root.
+fn read_repository_patterns(repository: &Path) -> Gitignore {
+    let exclude = repository.join(".git").join("info").join("exclude");
+
+    build_matcher(repository, gitconfig_excludes_path().into_iter().chain([exclude]))
+}
+
+fn read_patterns(dir: &Path) -> Gitignore {
+    build_matcher(dir, IGNORE_FILES.iter().map(|name| dir.join(name)))
+}
+
+fn decision(matched: Match<&::ignore::gitignore::Glob>) -> Option<bool> {
+    match matched {
+        Match::None => None,
+        Match::Ignore(_) => Some(true),
+        Match::Whitelist(_) => Some(false),
+    }
+}
+
+/// The ignore rules of a workspace, read lazily per directory. As in git, ignore
+/// files from the repository root down apply, including those above the
+/// workspace when it is a folder within a repository.
+///
+/// Unless `show_ignored` is set, ignored items are left out of listings;
+/// otherwise they are listed and flagged so the sidebar can grey them out.
+pub(crate) struct IgnoreRules {
+    root: PathBuf,
+    /// Topmost directory whose ignore files apply: the repository root, or the
+    /// workspace root outside a repository.
+    top: PathBuf,
+    show_ignored: bool,
+    repository: Gitignore,
+    directories: HashMap<PathBuf, Gitignore>,
+}
+
+impl IgnoreRules {
+    pub(crate) fn new(root: &Path, show_ignored: bool) -> Self {
+        let top = repository_root(root);
+        Self {
+            root: root.to_path_buf(),
+            show_ignored,
+            repository: read_repository_patterns(&top),
+            top,
+            directories: HashMap::new(),
+        }
+    }
+
+    /// Decision of the highest-precedence pattern matching `path` itself, if any.
+    fn matches(&mut self, path: &Path, is_dir: bool) -> Option<bool> {
+        let parent = path.parent()?;
+        let mut directories: Vec<PathBuf> = parent
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&self.top))
+            .map(Path::to_path_buf)
+            .collect();
+        directories.reverse();
+
+        // Deeper ignore files take precedence, as in git.
+        let mut result = decision(self.repository.matched(path, is_dir));
+
+        for dir in directories {
+            let patterns = self
+                .directories
+                .entry(dir)
+                .or_insert_with_key(|dir| read_patterns(dir));
+
+            result = decision(patterns.matched(path, is_dir)).or(result);
+        }
+
+        result
+    }
+
+    /// Whether `path` or any folder containing it is ignored. As in git, nothing
+    /// inside an ignored folder can be re-included.
+    pub(crate) fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        let parts: Vec<_> = relative.iter().collect();
+        let mut current = self.root.clone();
+
+        for (index, part) in parts.iter().enumerate() {
+            current.push(part);
+            let last = index + 1 == parts.len();
+            if self.matches(&current, !last || is_dir) == Some(true) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Whether to list `path`: `None` to leave it out, otherwise whether it is ignored.
+    pub(crate) fn listed(&mut self, path: &Path, is_dir: bool) -> Option<bool> {
+        let ignored = self.is_ignored(path, is_dir);
+        (self.show_ignored || !ignored).then_some(ignored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IgnoreRules;
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+
+    #[test]
+    fn ignore_rules_follow_gitignore_glob_rules() {
+        let root = make_temp_dir("ignore-globs");
+        fs::write(
+            root.join(".gitignore"),
+            "*.log\n/build\ndoc/*.md\n**/cache\nout/**\nfile[0-9].md\n\\#notes\n# comment\n",
+        )
+        .expect("failed to write gitignore");
+
+        let mut rules = IgnoreRules::new(&root, false);
+        assert!(rules.is_ignored(&root.join("debug.log"), false));
+        assert!(rules.is_ignored(&root.join("logs").join("debug.log"), false));
+        assert!(rules.is_ignored(&root.join("build"), true));
+        assert!(!rules.is_ignored(&root.join("src").join("build"), true));
+        assert!(rules.is_ignored(&root.join("doc").join("a.md"), false));
+        assert!(!rules.is_ignored(&root.join("doc").join("sub").join("a.md"), false));
+        assert!(rules.is_ignored(&root.join("a").join("b").join("cache"), true));
+        assert!(rules.is_ignored(&root.join("out").join("x").join("y.md"), false));
+        assert!(rules.is_ignored(&root.join("file7.md"), false));
+        assert!(!rules.is_ignored(&root.join("fileA.md"), false));
+        assert!(rules.is_ignored(&root.join("#notes"), false));
+        assert!(!rules.is_ignored(&root.join("# comment"), false));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn ignore_rules_apply_the_repository_exclude_file() {
+        let root = make_temp_dir("ignore-exclude");
+        let workspace = root.join("notes");
+        fs::create_dir_all(root.join(".git").join("info")).expect("failed to create git folder");
+        fs::create_dir_all(&workspace).expect("failed to create workspace");
+        fs::write(root.join(".git").join("info").join("exclude"), "notes/private/\n*.bak\n")
+            .expect("failed to write exclude file");
+        fs::write(workspace.join(".gitignore"), "!keep.bak\n").expect("failed to write gitignore");
+
+        let mut rules = IgnoreRules::new(&workspace, false);
+        assert!(rules.is_ignored(&workspace.join("private"), true));
+        assert!(rules.is_ignored(&workspace.join("old.bak"), false));
+        assert!(!rules.is_ignored(&workspace.join("keep.bak"), false));
+        assert!(!rules.is_ignored(&workspace.join("public.md"), false));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn ignore_rules_apply_ignore_files_above_a_workspace_within_a_repository() {
+        let root = make_temp_dir("ignore-above");
+        let workspace = root.join("docs").join("site");
+        fs::create_dir_all(root.join(".git")).expect("failed to create git folder");
+        fs::create_dir_all(&workspace).expect("failed to create workspace");
+        fs::write(root.join(".gitignore"), "node_modules/
+/docs/site/target/
+")
+            .expect("failed to write gitignore");
+        fs::write(root.join("docs").join(".ignore"), "*.tmp
+").expect("failed to write ignore file");
+        fs::write(workspace.join(".gitignore"), "!keep.tmp
+").expect("failed to write workspace gitignore");
+
+        let mut rules = IgnoreRules::new(&workspace, false);
+        assert!(rules.is_ignored(&workspace.join("node_modules"), true));
+        assert!(rules.is_ignored(&workspace.join("target").join("index.md"), false));
+        assert!(rules.is_ignored(&workspace.join("scratch.tmp"), false));
+        assert!(!rules.is_ignored(&workspace.join("keep.tmp"), false));
+        assert!(!rules.is_ignored(&workspace.join("notes.md"), false));
+
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn ignore_rules_combine_nested_files_and_negations() {
+        let root = make_temp_dir("ignore-rules");
+        fs::create_dir_all(root.join("docs").join("drafts")).expect("failed to create folders");
+        fs::write(root.join(".gitignore"), "node_modules/\n*.tmp\nbuild\n").expect("failed to write gitignore");
+        fs::write(root.join(".keaignore"), "drafts/\n").expect("failed to write keaignore");
+        fs::write(root.join("docs").join(".ignore"), "!keep.tmp\n").expect("failed to write nested ignore");
+
+        let mut rules = IgnoreRules::new(&root, false);
+        assert!(rules.is_ignored(&root.join("node_modules"), true));
+        assert!(!rules.is_ignored(&root.join("node_modules"), false));
+        assert!(rules.is_ignored(&root.join("build").join("index.md"), false));
+        assert!(rules.is_ignored(&root.join("scratch.tmp"), false));
+        assert!(!rules.is_ignored(&root.join("docs").join("keep.tmp"), false));
+        assert!(rules.is_ignored(&root.join("docs").join("drafts").join("keep.tmp"), false));
+        assert!(!rules.is_ignored(&root.join("docs").join("notes.md"), false));
+        assert_eq!(rules.listed(&root.join("scratch.tmp"), false), None);
+
+        let mut shown = IgnoreRules::new(&root, true);
+        assert_eq!(shown.listed(&root.join("scratch.tmp"), false), Some(true));
+        assert_eq!(shown.listed(&root.join("notes.md"), false), Some(false));
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,736 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use super::encoding::decode_text;
+use super::error::CommandError;
+use super::file::{
+    atomic_write_file, content_hash, is_markdown_file, read_modified_millis, workspace_files,
+};
+use super::paths::IpcPath;
+use super::sandbox::check_access;
+use crate::{SearchIndexRegistry, WorkspaceAccess};
+
+/// Bumped whenever the persisted layout or tokenizer changes; older indexes are rebuilt.
+const INDEX_FORMAT_VERSION: u32 = 2;
+
+/// Results returned by a query when the caller does not ask for a limit.
+const DEFAULT_QUERY_LIMIT: usize = 50;
+
+/// BM25 term-frequency saturation and length normalisation.
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+/// The log is rewritten from the indexed documents once it holds this many
+/// entries per document, most of them superseded.
+const COMPACT_RATIO: usize = 2;
+
+/// Positions of each term within one document.
+type DocumentPostings = BTreeMap<String, Vec<u32>>;
+
+#[derive(Debug, Clone)]
+struct IndexedDocument {
+    modified: Option<u64>,
+    /// Number of terms, for BM25 length normalisation.
+    length: usize,
+    /// Distinct terms, so the document's postings can be dropped again.
+    terms: Vec<String>,
+}
+
+/// One line of the persisted log. A later entry for a path replaces earlier ones.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "entry", rename_all = "snake_case")]
+enum LogEntry {
+    Header {
+        version: u32,
+    },
+    Indexed {
+        path: String,
+        modified: Option<u64>,
+        length: usize,
+        postings: DocumentPostings,
+    },
+    Removed {
+        path: String,
+    },
+}
+
+/// Full-text index of a workspace's markdown documents.
+///
+/// Every change is appended to a log of per-document postings as it happens,
+/// so nothing is lost when Kea exits without closing the index. The log is
+/// only rewritten when superseded entries outweigh the live ones. Files whose
+/// path is not valid UTF-8 are not indexed.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    documents: HashMap<String, IndexedDocument>,
+    postings: BTreeMap<String, HashMap<String, Vec<u32>>>,
+    /// Where changes are persisted; `None` for an index kept in memory only.
+    log: Option<PathBuf>,
+    /// Changes not yet appended to the log.
+    pending: Vec<LogEntry>,
+    /// Entries in the log, superseded ones included; 0 when it must be rewritten.
+    logged: usize,
+}
+
+/// A ranked query result.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexHit {
+    pub path: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexStats {
+    pub documents: usize,
+    pub terms: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum QueryClause {
+    Term(String),
+    Prefix(String),
+    Phrase(Vec<String>),
+}
+
+/// Lowercased alphanumeric words, in document order.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Parse `"exact phrase"`, `prefix*` and plain terms. Every clause must match.
+fn parse_query(query: &str) -> Vec<QueryClause> {
+    let mut clauses = Vec::new();
+
+    for (index, part) in query.split('"').enumerate() {
+        if index % 2 == 1 {
+            let terms = tokenize(part);
+            match terms.len() {
+                0 => {}
+                1 => clauses.extend(terms.into_iter().map(QueryClause::Term)),
+                _ => clauses.push(QueryClause::Phrase(terms)),
+            }
+            continue;
+        }
+
+        for word in part.split_whitespace() {
+            let (word, is_prefix) = match word.strip_suffix('*') {
+                Some(stem) => (stem, true),
+                None => (word, false),
+            };
+
+            let mut terms = tokenize(word);
+            match (terms.len(), is_prefix) {
+                (0, _) => {}
+                (1, true) => clauses.push(QueryClause::Prefix(terms.remove(0))),
+                (1, false) => clauses.push(QueryClause::Term(terms.remove(0))),
+                _ => clauses.push(QueryClause::Phrase(terms)),
+            }
+        }
+    }
+
+    clauses
+}
+
+fn log_line(entry: &LogEntry) -> Result<String, CommandError> {
+    serde_json::to_string(entry)
+        .map(|json| json + "\n")
+        .map_err(|e| format!("Failed to serialize search index: {}", e).into())
+}
+
+impl SearchIndex {
+    #[cfg(test)]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the index persisted in `log`, starting fresh when it is missing or
+    /// outdated. Later changes are appended to the same log.
+    fn load(log: &Path) -> Self {
+        let mut index = Self {
+            log: Some(log.to_path_buf()),
+            ..Self::default()
+        };
+
+        let Ok(content) = fs::read_to_string(log) else {
+            return index;
+        };
+
+        let mut lines = content.lines();
+        let header = lines.next().and_then(|line| serde_json::from_str::<LogEntry>(line).ok());
+        if !matches!(header, Some(LogEntry::Header { version }) if version == INDEX_FORMAT_VERSION) {
+            return index;
+        }
+
+        for line in lines {
+            match serde_json::from_str::<LogEntry>(line) {
+                Ok(LogEntry::Indexed { path, modified, length, postings }) => {
+                    index.add_document(path, modified, length, postings);
+                }
+                Ok(LogEntry::Removed { path }) => {
+                    index.forget(&path);
+                }
+                _ => {}
+            }
+        }
+
+        // A write cut short leaves a partial last line; rewrite rather than append after it.
+        if content.ends_with('\n') {
+            index.logged = content.lines().count();
+        }
+
+        index
+    }
+
+    /// Persist pending changes, appending them to the log, or rewriting it when
+    /// it is new, damaged or mostly superseded.
+    fn flush(&mut self) -> Result<(), CommandError> {
+        let Some(log) = self.log.clone() else {
+            self.pending.clear();
+            return Ok(());
+        };
+
+        if self.logged > 0 && self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let superseded = self.logged + self.pending.len() > COMPACT_RATIO * self.documents.len().max(1);
+        if self.logged == 0 || superseded {
+            return self.compact(&log);
+        }
+
+        let mut lines = String::new();
+        for entry in &self.pending {
+            lines.push_str(&log_line(entry)?);
+        }
+
+        OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .map_err(|e| CommandError::io(&log, "Failed to update search index", e))?;
+
+        self.logged += self.pending.len();
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Rewrite the log with one entry per indexed document.
+    fn compact(&mut self, log: &Path) -> Result<(), CommandError> {
+        if let Some(parent) = log.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| CommandError::io(parent, "Failed to create search index directory", e))?;
+        }
+
+        let mut postings: HashMap<&str, DocumentPostings> = HashMap::new();
+        for (term, documents) in &self.postings {
+            for (path, positions) in documents {
+                postings
+                    .entry(path.as_str())
+                    .or_default()
+                    .insert(term.clone(), positions.clone());
+            }
+        }
+
+        let mut content = log_line(&LogEntry::Header {
+            version: INDEX_FORMAT_VERSION,
+        })?;
+        for (path, document) in &self.documents {
+            content.push_str(&log_line(&LogEntry::Indexed {
+                path: path.clone(),
+                modified: document.modified,
+                length: document.length,
+                postings: postings.remove(path.as_str()).unwrap_or_default(),
+            })?);
+        }
+
+        atomic_write_file(log, &content)?;
+        self.logged = self.documents.len() + 1;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn stats(&self) -> IndexStats {
+        IndexStats {
+            documents: self.documents.len(),
+            terms: self.postings.len(),
+        }
+    }
+
+    fn add_document(&mut self, path: String, modified: Option<u64>, length: usize, postings: DocumentPostings) {
+        self.forget(&path);
+
+        let terms = postings.keys().cloned().collect();
+        for (term, positions) in postings {
+            self.postings.entry(term).or_default().insert(path.clone(), positions);
+        }
+        self.documents.insert(path, IndexedDocument { modified, length, terms });
+    }
+
+    /// Drop `path` from memory only. Returns whether it was indexed.
+    fn forget(&mut self, path: &str) -> bool {
+        let Some(document) = self.documents.remove(path) else {
+            return false;
+        };
+
+        for term in &document.terms {
+            if let Some(postings) = self.postings.get_mut(term) {
+                postings.remove(path);
+                if postings.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Index `content` as the current text of `path`, replacing any earlier version.
+    fn insert(&mut self, path: &str, content: &str, modified: Option<u64>) {
+        let terms = tokenize(content);
+        let mut postings = DocumentPostings::new();
+        for (position, term) in terms.iter().enumerate() {
+            postings.entry(term.clone()).or_default().push(position as u32);
+        }
+
+        if self.log.is_some() {
+            self.pending.push(LogEntry::Indexed {
+                path: path.to_string(),
+                modified,
+                length: terms.len(),
+                postings: postings.clone(),
+            });
+        }
+        self.add_document(path.to_string(), modified, terms.len(), postings);
+    }
+
+    fn remove(&mut self, path: &str) {
+        if self.forget(path) && self.log.is_some() {
+            self.pending.push(LogEntry::Removed {
+                path: path.to_string(),
+            });
+        }
+    }
+
+    /// Re-read a file that changed on disk, or drop it (and anything below it) when gone.
+    fn update_path(&mut self, path: &Path) {
+        let files = if path.is_dir() {
+            workspace_files(path)
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        let mut indexed = HashSet::new();
+        for file in files {
+            if self.index_file(&file) {
+                indexed.insert(file);
+            }
+        }
+
+        let gone: Vec<String> = self
+            .documents
+            .keys()
+            .filter(|document| Path::new(document).starts_with(path))
+            .filter(|document| !indexed.contains(Path::new(document)))
+            .cloned()
+            .collect();
+        for document in gone {
+            self.remove(&document);
+        }
+    }
+
+    /// Index the current text of `file`. Returns whether it was indexed.
+    fn index_file(&mut self, file: &Path) -> bool {
+        if !is_markdown_file(file) {
+            return false;
+        }
+
+        let Some(path) = file.to_str() else {
+            return false;
+        };
+
+        match fs::read(file) {
+            Ok(bytes) => {
+                self.insert(path, &decode_text(&bytes).0, read_modified_millis(file));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Bring the index in line with the workspace, re-reading only files whose
+    /// modification time changed since they were indexed.
+    fn refresh(&mut self, root: &Path) {
+        let mut seen = HashSet::new();
+
+        for file in workspace_files(root) {
+            let Some(path) = file.to_str().filter(|_| is_markdown_file(&file)) else {
+                continue;
+            };
+
+            let path = path.to_string();
+            let modified = read_modified_millis(&file);
+            let unchanged = self
+                .documents
+                .get(&path)
+                .is_some_and(|document| modified.is_some() && document.modified == modified);
+
+            if !unchanged {
+                self.index_file(&file);
+            }
+            seen.insert(path);
+        }
+
+        let stale: Vec<String> = self
+            .documents
+            .keys()
+            .filter(|path| !seen.contains(*path))
+            .cloned()
+            .collect();
+        for path in stale {
+            self.remove(&path);
+        }
+    }
+
+    /// Term frequency per document for a single clause.
+    fn clause_frequencies(&self, clause: &QueryClause) -> HashMap<String, usize> {
+        let mut frequencies: HashMap<String, usize> = HashMap::new();
+
+        match clause {
+            QueryClause::Term(term) => {
+                if let Some(postings) = self.postings.get(term) {
+                    for (path, positions) in postings {
+                        frequencies.insert(path.clone(), positions.len());
+                    }
+                }
+            }
+            QueryClause::Prefix(prefix) => {
+                let expanded = self
+                    .postings
+                    .range(prefix.clone()..)
+                    .take_while(|(term, _)| term.starts_with(prefix.as_str()));
+                for (_, postings) in expanded {
+                    for (path, positions) in postings {
+                        *frequencies.entry(path.clone()).or_default() += positions.len();
+                    }
+                }
+            }
+            QueryClause::Phrase(terms) => {
+                let term_postings: Option<Vec<&HashMap<String, Vec<u32>>>> =
+                    terms.iter().map(|term| self.postings.get(term)).collect();
+                let Some(term_postings) = term_postings else {
+                    return frequencies;
+                };
+
+                for (path, starts) in term_postings[0] {
+                    let occurrences = starts
+                        .iter()
+                        .filter(|start| {
+                            term_postings[1..].iter().enumerate().all(|(offset, postings)| {
+                                postings.get(path).is_some_and(|positions| {
+                                    positions.binary_search(&(**start + offset as u32 + 1)).is_ok()
+                                })
+                            })
+                        })
+                        .count();
+
+                    if occurrences > 0 {
+                        frequencies.insert(path.clone(), occurrences);
+                    }
+                }
+            }
+        }
+
+        frequencies
+    }
+
+    /// Documents matching every clause of `query`, ranked by BM25.
+    fn query(&self, query: &str, limit: usize) -> Vec<IndexHit> {
+        let clauses = parse_query(query);
+        if clauses.is_empty() || self.documents.is_empty() {
+            return Vec::new();
+        }
+
+        let total = self.documents.len() as f64;
+        let average_length = self
+            .documents
+            .values()
+            .map(|document| document.length)
+            .sum::<usize>() as f64
+            / total;
+
+        let mut scores: Option<HashMap<String, f64>> = None;
+
+        for clause in &clauses {
+            let frequencies = self.clause_frequencies(clause);
+            let matching = frequencies.len() as f64;
+            let idf = (1.0 + (total - matching + 0.5) / (matching + 0.5)).ln();
+
+            let mut next = HashMap::new();
+            for (path, frequency) in frequencies {
+                let previous = match &scores {
+                    Some(scores) => match scores.get(&path) {
+                        Some(score) => *score,
+                        None => continue,
+                    },
+                    None => 0.0,
+                };
+
+                let length = self.documents[&path].length as f64;
+                let frequency = frequency as f64;
+                let weight = frequency * (BM25_K1 + 1.0)
+                    / (frequency + BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length.max(1.0)));
+
+                next.insert(path, previous + idf * weight);
+            }
+
+            scores = Some(next);
+        }
+
+        let mut hits: Vec<IndexHit> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(path, score)| IndexHit { path, score })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        hits.truncate(limit);
+
+        hits
+    }
+}
+
+/// Where the index for `root` is persisted.
+fn index_file_path(app: &AppHandle, root: &Path) -> Result<PathBuf, CommandError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+
+    Ok(data_dir
+        .join("search-index")
+        .join(format!("{}.jsonl", content_hash(root.as_os_str().as_encoded_bytes()))))
+}
+
+/// Apply changed paths reported by the workspace watcher to the open index for
+/// `root`, persisting them straight away.
+pub(crate) fn update_indexed_paths(registry: &SearchIndexRegistry, root: &Path, paths: &[PathBuf]) {
+    let Ok(mut indexes) = registry.indexes.lock() else {
+        return;
+    };
+
+    if let Some(index) = indexes.get_mut(root) {
+        for path in paths {
+            index.update_path(path);
+        }
+        let _ = index.flush();
+    }
+}
+
+/// Re-index content Kea itself just saved in every open index containing `path`.
+pub(crate) fn index_own_write(registry: &SearchIndexRegistry, path: &Path, content: &str) {
+    let Some(path_str) = path.to_str().filter(|_| is_markdown_file(path)) else {
+        return;
+    };
+
+    let Ok(mut indexes) = registry.indexes.lock() else {
+        return;
+    };
+
+    for (root, index) in indexes.iter_mut() {
+        if path.starts_with(root) {
+            index.insert(path_str, content, read_modified_millis(path));
+            let _ = index.flush();
+        }
+    }
+}
+
+/// Load or build the search index for a workspace and keep it up to date.
+///
+/// The persisted index is refreshed against the files on disk, so changes made
+/// while Kea was closed are picked up without a full rebuild.
+#[tauri::command]
+pub async fn open_search_index(
+    app: AppHandle,
+    state: State<'_, SearchIndexRegistry>,
+    root: IpcPath,
+) -> Result<IndexStats, CommandError> {
+    let root = root.to_path_buf();
+    check_access(&app.state::<WorkspaceAccess>(), &root)?;
+    if !root.is_dir() {
+        return Err(CommandError::Other(format!("{} is not a directory", root.display())));
+    }
+
+    let file = index_file_path(&app, &root)?;
+    // Indexes from before the log format were a single JSON file next to it.
+    let _ = fs::remove_file(file.with_extension("json"));
+
+    let mut index = SearchIndex::load(&file);
+    index.refresh(&root);
+    index.flush()?;
+
+    let stats = index.stats();
+    state
+        .indexes
+        .lock()
+        .map_err(|_| "Failed to lock search index registry")?
+        .insert(root, index);
+
+    Ok(stats)
+}
+
+/// Query the open index of a workspace, returning ranked matching documents.
+///
+/// Supports plain terms, `"quoted phrases"` and `prefix*` terms; every part
+/// of the query must match.
+#[tauri::command]
+pub async fn query_search_index(
+    state: State<'_, SearchIndexRegistry>,
+    root: IpcPath,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<IndexHit>, CommandError> {
+    let indexes = state
+        .indexes
+        .lock()
+        .map_err(|_| "Failed to lock search index registry")?;
+
+    let index = indexes
+        .get(&root.to_path_buf())
+        .ok_or("Search index is not open for that workspace")?;
+
+    Ok(index.query(&query, limit.unwrap_or(DEFAULT_QUERY_LIMIT)))
+}
+
+/// Persist and release the index of a workspace.
+#[tauri::command]
+pub async fn close_search_index(
+    state: State<'_, SearchIndexRegistry>,
+    root: IpcPath,
+) -> Result<(), CommandError> {
+    let root = root.to_path_buf();
+    let index = state
+        .indexes
+        .lock()
+        .map_err(|_| "Failed to lock search index registry")?
+        .remove(&root);
+
+    match index {
+        Some(mut index) => index.flush(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_query, QueryClause, SearchIndex, COMPACT_RATIO};
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+
+    #[test]
+    fn parse_query_recognises_phrases_prefixes_and_terms() {
+        assert_eq!(
+            parse_query("\"Green Tea\" brew* cups-of"),
+            vec![
+                QueryClause::Phrase(vec!["green".to_string(), "tea".to_string()]),
+                QueryClause::Prefix("brew".to_string()),
+                QueryClause::Phrase(vec!["cups".to_string(), "of".to_string()]),
+            ]
+        );
+        assert_eq!(parse_query("\"solo\" *"), vec![QueryClause::Term("solo".to_string())]);
+    }
+
+    #[test]
+    fn query_matches_phrases_and_prefixes_and_ranks_by_relevance() {
+        let mut index = SearchIndex::new();
+        index.insert("/ws/tea.md", "Green tea. Brewing green tea at home.", None);
+        index.insert("/ws/coffee.md", "Tea is green, coffee is brewed strong.", None);
+        index.insert("/ws/misc.md", "Nothing relevant here.", None);
+
+        let phrase = index.query("\"green tea\"", 10);
+        assert_eq!(phrase.len(), 1);
+        assert_eq!(phrase[0].path, "/ws/tea.md");
+
+        let prefix = index.query("brew* green", 10);
+        let paths: Vec<&str> = prefix.iter().map(|hit| hit.path.as_str()).collect();
+        assert_eq!(paths, vec!["/ws/tea.md", "/ws/coffee.md"]);
+
+        index.remove("/ws/tea.md");
+        assert!(index.query("\"green tea\"", 10).is_empty());
+        assert_eq!(index.query("home", 10), Vec::new());
+    }
+
+    #[test]
+    fn persisted_index_is_refreshed_incrementally() {
+        let root = make_temp_dir("index-refresh");
+        let store = root.join(".index").join("index.jsonl");
+        fs::write(root.join("a.md"), "alpha notes").expect("failed to write first note");
+        fs::write(root.join("b.md"), "beta notes").expect("failed to write second note");
+
+        let mut index = SearchIndex::load(&store);
+        index.refresh(&root);
+        index.flush().expect("index should persist");
+
+        fs::remove_file(root.join("b.md")).expect("failed to remove second note");
+        fs::write(root.join("c.md"), "gamma notes").expect("failed to write third note");
+
+        let mut reloaded = SearchIndex::load(&store);
+        assert_eq!(reloaded.query("beta", 10).len(), 1);
+        reloaded.refresh(&root);
+
+        assert_eq!(reloaded.stats().documents, 2);
+        assert!(reloaded.query("beta", 10).is_empty());
+        assert_eq!(reloaded.query("gamma", 10).len(), 1);
+        assert_eq!(reloaded.query("notes", 10).len(), 2);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn index_log_appends_each_change_and_decodes_legacy_notes() {
+        let root = make_temp_dir("index-log");
+        let store = root.join(".index").join("index.jsonl");
+        fs::write(root.join("a.md"), "alpha notes").expect("failed to write first note");
+        // "café" in Windows-1252, which is not valid UTF-8.
+        fs::write(root.join("legacy.md"), b"caf\xe9 notes").expect("failed to write legacy note");
+
+        let mut index = SearchIndex::load(&store);
+        index.refresh(&root);
+        index.flush().expect("index should persist");
+        assert_eq!(index.query("café", 10).len(), 1);
+        let written = fs::read_to_string(&store).expect("log should be readable");
+
+        fs::write(root.join("a.md"), "omega notes").expect("failed to update first note");
+        index.update_path(&root.join("a.md"));
+        index.flush().expect("index should persist");
+        let appended = fs::read_to_string(&store).expect("log should be readable");
+        assert!(appended.starts_with(&written));
+        assert_eq!(appended.lines().count(), written.lines().count() + 1);
+
+        // Dropped without closing, as after a crash.
+        drop(index);
+        let mut reloaded = SearchIndex::load(&store);
+        assert_eq!(reloaded.query("omega", 10).len(), 1);
+        assert!(reloaded.query("alpha", 10).is_empty());
+
+        for round in 0..10 {
+            fs::write(root.join("a.md"), format!("round {}", round)).expect("failed to update first note");
+            reloaded.update_path(&root.join("a.md"));
+            reloaded.flush().expect("index should persist");
+        }
+        let compacted = fs::read_to_string(&store).expect("log should be readable");
+        assert!(compacted.lines().count() <= COMPACT_RATIO * 2);
+        assert_eq!(SearchIndex::load(&store).query("round", 10).len(), 1);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,365 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, State};
+use super::error::CommandError;
+use super::file::{delete_path, restore_from_trash};
+use super::history::move_history;
+use super::links::{reapply_link_edits, revert_link_edits, LinkEdit};
+use super::paths::IpcPath;
+use super::quick_open::invalidate_quick_open_containing;
+use crate::{FileOperationJournal, QuickOpenRegistry};
+
+/// How many operations are kept for undo before the oldest are dropped.
+const MAX_JOURNAL_ENTRIES: usize = 100;
+
+/// A reversible filesystem operation performed from the sidebar.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileOperation {
+    /// A file or folder was created; `trash_id` is set while it is undone.
+    /// `empty` records whether it was empty when undone, in which case redo can
+    /// create it again where the trash cannot give it back.
+    Create {
+        path: IpcPath,
+        is_dir: bool,
+        trash_id: Option<String>,
+        #[serde(default)]
+        empty: bool,
+    },
+    /// A rename or move from one path to another, with the links rewritten to
+    /// follow it.
+    Move {
+        from: IpcPath,
+        to: IpcPath,
+        #[serde(skip)]
+        link_edits: Vec<LinkEdit>,
+    },
+    /// An item was moved to the trash.
+    Delete { path: IpcPath, trash_id: String },
+}
+
+/// Undo and redo stacks of filesystem operations.
+#[derive(Debug, Default)]
+pub struct FileOperationHistory {
+    undo: Vec<FileOperation>,
+    redo: Vec<FileOperation>,
+}
+
+impl FileOperationHistory {
+    /// Record a newly performed operation, discarding anything that could be redone.
+    pub fn record(&mut self, operation: FileOperation) {
+        self.undo.push(operation);
+        if self.undo.len() > MAX_JOURNAL_ENTRIES {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Reverse the most recent operation. An operation that can no longer be
+    /// reversed is dropped so older entries stay reachable; after any other
+    /// failure it stays on the stack to be tried again.
+    fn undo(&mut self) -> Result<Option<FileOperation>, CommandError> {
+        let Some(operation) = self.undo.pop() else {
+            return Ok(None);
+        };
+
+        match undo_operation(operation.clone()) {
+            Ok(undone) => {
+                self.redo.push(undone.clone());
+                Ok(Some(undone))
+            }
+            Err(error) => {
+                if !is_stale(&error) {
+                    self.undo.push(operation);
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Re-apply the most recently undone operation, keeping it for another try
+    /// unless it can no longer be re-applied.
+    fn redo(&mut self) -> Result<Option<FileOperation>, CommandError> {
+        let Some(operation) = self.redo.pop() else {
+            return Ok(None);
+        };
+
+        match redo_operation(operation.clone()) {
+            Ok(redone) => {
+                self.undo.push(redone.clone());
+                Ok(Some(redone))
+            }
+            Err(error) => {
+                if !is_stale(&error) {
+                    self.redo.push(operation);
+                }
+                Err(error)
+            }
+        }
+    }
+}
+
+impl FileOperation {
+    /// The paths the operation adds or removes.
+    fn paths(&self) -> Vec<&IpcPath> {
+        match self {
+            FileOperation::Create { path, .. } | FileOperation::Delete { path, .. } => vec![path],
+            FileOperation::Move { from, to, .. } => vec![from, to],
+        }
+    }
+}
+
+/// Whether an undo or redo failed because the filesystem moved on since the
+/// operation, rather than for a reason that may pass.
+fn is_stale(error: &CommandError) -> bool {
+    matches!(error, CommandError::NotFound(_) | CommandError::AlreadyExists(_))
+}
+
+/// Whether the item at `path` is an empty file or folder.
+fn is_empty_item(path: &Path, is_dir: bool) -> bool {
+    if is_dir {
+        fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none())
+    } else {
+        fs::metadata(path).is_ok_and(|metadata| metadata.len() == 0)
+    }
+}
+
+/// Forget the Quick Open path lists an undone or redone operation made stale.
+fn invalidate_operation_paths(quick_open: &QuickOpenRegistry, operation: &Option<FileOperation>) {
+    for path in operation.iter().flat_map(FileOperation::paths) {
+        invalidate_quick_open_containing(quick_open, &path.to_path_buf());
+    }
+}
+
+/// Record an operation in the shared journal.
+pub(crate) fn record_operation(journal: &FileOperationJournal, operation: FileOperation) {
+    if let Ok(mut history) = journal.history.lock() {
+        history.record(operation);
+    }
+}
+
+fn move_path(from: &Path, to: &Path) -> Result<(), CommandError> {
+    if !from.exists() {
+        return Err(CommandError::NotFound(IpcPath::from(from)));
+    }
+
+    if to.exists() {
+        return Err(CommandError::AlreadyExists(IpcPath::from(to)));
+    }
+
+    fs::rename(from, to).map_err(|e| CommandError::io(from, "Failed to move", e))
+}
+
+fn undo_operation(operation: FileOperation) -> Result<FileOperation, CommandError> {
+    match operation {
+        FileOperation::Create { path, is_dir, .. } => {
+            let created = path.to_path_buf();
+            let empty = is_empty_item(&created, is_dir);
+            let deleted = delete_path(&created, false)?;
+            Ok(FileOperation::Create {
+                path,
+                is_dir,
+                trash_id: deleted.trash_id,
+                empty,
+            })
+        }
+        FileOperation::Move { from, to, link_edits } => {
+            move_path(&to.to_path_buf(), &from.to_path_buf())?;
+            Ok(FileOperation::Move { from, to, link_edits })
+        }
+        FileOperation::Delete { path, trash_id } => {
+            restore_from_trash(&trash_id)?;
+            Ok(FileOperation::Delete { path, trash_id })
+        }
+    }
+}
+
+fn redo_operation(operation: FileOperation) -> Result<FileOperation, CommandError> {
+    match operation {
+        FileOperation::Create {
+            path,
+            is_dir,
+            trash_id,
+            empty,
+        } => {
+            let created = path.to_path_buf();
+            if created.exists() {
+                return Err(CommandError::AlreadyExists(path));
+            }
+
+            match trash_id {
+                Some(trash_id) => {
+                    restore_from_trash(&trash_id)?;
+                }
+                // Without a trash id the item can only be recreated if nothing is lost.
+                None if !empty => {
+                    return Err("Created item cannot be restored on this platform".into());
+                }
+                None if is_dir => {
+                    fs::create_dir_all(&created)
+                        .map_err(|e| CommandError::io(&created, "Failed to create folder", e))?;
+                }
+                None => {
+                    fs::File::create(&created)
+                        .map_err(|e| CommandError::io(&created, "Failed to create file", e))?;
+                }
+            }
+
+            Ok(FileOperation::Create {
+                path,
+                is_dir,
+                trash_id: None,
+                empty: false,
+            })
+        }
+        FileOperation::Move { from, to, link_edits } => {
+            move_path(&from.to_path_buf(), &to.to_path_buf())?;
+            Ok(FileOperation::Move { from, to, link_edits })
+        }
+        FileOperation::Delete { path, .. } => {
+            let deleted = delete_path(&path.to_path_buf(), false)?;
+            let trash_id = deleted
+                .trash_id
+                .ok_or("Deleted item cannot be restored on this platform")?;
+            Ok(FileOperation::Delete { path, trash_id })
+        }
+    }
+}
+
+/// Undo the most recent file operation, returning it or `None` when there is nothing to undo.
+///
+/// Undoing a rename or move also puts back the links it rewrote, in files not edited since.
+#[tauri::command]
+pub async fn undo_file_operation(
+    app: AppHandle,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+) -> Result<Option<FileOperation>, CommandError> {
+    let operation = journal
+        .history
+        .lock()
+        .map_err(|_| "Failed to lock operation journal")?
+        .undo()?;
+    invalidate_operation_paths(&quick_open, &operation);
+    if let Some(FileOperation::Move { from, to, link_edits }) = &operation {
+        let (from, to) = (from.to_path_buf(), to.to_path_buf());
+        move_history(&app, &to, &from);
+        revert_link_edits(&app, link_edits, &from, &to);
+    }
+
+    Ok(operation)
+}
+
+/// Redo the most recently undone file operation, returning it or `None` when there is nothing to redo.
+#[tauri::command]
+pub async fn redo_file_operation(
+    app: AppHandle,
+    journal: State<'_, FileOperationJournal>,
+    quick_open: State<'_, QuickOpenRegistry>,
+) -> Result<Option<FileOperation>, CommandError> {
+    let operation = journal
+        .history
+        .lock()
+        .map_err(|_| "Failed to lock operation journal")?
+        .redo()?;
+    invalidate_operation_paths(&quick_open, &operation);
+    if let Some(FileOperation::Move { from, to, link_edits }) = &operation {
+        move_history(&app, &from.to_path_buf(), &to.to_path_buf());
+        reapply_link_edits(&app, link_edits);
+    }
+
+    Ok(operation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileOperation, FileOperationHistory};
+    use crate::commands::paths::IpcPath;
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+
+    #[test]
+    fn history_undoes_and_redoes_moves() {
+        let root = make_temp_dir("journal-move");
+        let from = root.join("draft.md");
+        let to = root.join("final.md");
+        fs::write(&to, "moved").expect("failed to write moved file");
+
+        let mut history = FileOperationHistory::default();
+        history.record(FileOperation::Move {
+            from: IpcPath::from(&from),
+            to: IpcPath::from(&to),
+            link_edits: Vec::new(),
+        });
+
+        let undone = history.undo().expect("undo should succeed");
+        assert!(matches!(undone, Some(FileOperation::Move { .. })));
+        assert!(from.exists());
+        assert!(!to.exists());
+
+        history.redo().expect("redo should succeed");
+        assert!(!from.exists());
+        assert!(to.exists());
+
+        assert_eq!(history.redo().expect("redo should succeed"), None);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn history_drops_operations_that_can_no_longer_be_undone() {
+        let root = make_temp_dir("journal-stale");
+        let older_to = root.join("older.md");
+        fs::write(&older_to, "older").expect("failed to write file");
+
+        let mut history = FileOperationHistory::default();
+        history.record(FileOperation::Move {
+            from: IpcPath::from(root.join("older-original.md")),
+            to: IpcPath::from(&older_to),
+            link_edits: Vec::new(),
+        });
+        history.record(FileOperation::Move {
+            from: IpcPath::from(root.join("missing-original.md")),
+            to: IpcPath::from(root.join("missing.md")),
+            link_edits: Vec::new(),
+        });
+
+        assert!(history.undo().is_err());
+        assert!(history.undo().expect("older undo should succeed").is_some());
+        assert!(root.join("older-original.md").exists());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn redo_recreates_only_empty_items_without_a_trash_id_and_keeps_failed_entries() {
+        let root = make_temp_dir("journal-create");
+        let kept = root.join("kept.md");
+        let blank = root.join("blank.md");
+
+        let mut history = FileOperationHistory {
+            undo: Vec::new(),
+            redo: vec![FileOperation::Create {
+                path: IpcPath::from(&kept),
+                is_dir: false,
+                trash_id: None,
+                empty: false,
+            }],
+        };
+        assert!(history.redo().is_err());
+        assert!(!kept.exists());
+        assert_eq!(history.redo.len(), 1);
+
+        history.redo = vec![FileOperation::Create {
+            path: IpcPath::from(&blank),
+            is_dir: false,
+            trash_id: None,
+            empty: true,
+        }];
+        assert!(history.redo().expect("redo should succeed").is_some());
+        assert_eq!(fs::read(&blank).expect("file should be recreated"), b"");
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+use similar::{DiffTag, TextDiff};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+/// Line-ending style of a document and whether it ends with a line break.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LineStyle {
+    pub line_ending: LineEnding,
+    pub final_newline: bool,
+}
+
+/// Split `content` into lines and the line break ending each (empty for the last).
+fn split_lines(content: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = content;
+
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let Some(index) = rest.find(['\r', '\n']) else {
+            let line = rest;
+            rest = "";
+            return Some((line, ""));
+        };
+
+        let break_len = if rest[index..].starts_with("\r\n") { 2 } else { 1 };
+        let (line, after) = rest.split_at(index);
+        let (line_break, after) = after.split_at(break_len);
+        rest = after;
+        Some((line, line_break))
+    })
+}
+
+/// Whether `a` and `b` have the same lines, whatever their line endings.
+pub(crate) fn same_lines(a: &str, b: &str) -> bool {
+    split_lines(a).map(|(line, _)| line).eq(split_lines(b).map(|(line, _)| line))
+}
+
+/// The predominant line ending of `content`, LF when it has none.
+pub(crate) fn detect_line_style(content: &str) -> LineStyle {
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+
+    for (_, line_break) in split_lines(content) {
+        match line_break {
+            "\n" => lf += 1,
+            "\r\n" => crlf += 1,
+            "\r" => cr += 1,
+            _ => {}
+        }
+    }
+
+    let line_ending = if crlf > lf && crlf >= cr {
+        LineEnding::Crlf
+    } else if cr > lf && cr > crlf {
+        LineEnding::Cr
+    } else {
+        LineEnding::Lf
+    };
+
+    LineStyle {
+        line_ending,
+        final_newline: content.ends_with(['\r', '\n']),
+    }
+}
+
+/// Rewrite every line break in `content` to `style`, then add or drop a single
+/// trailing line break to match its final-newline state. Empty content is left alone.
+pub(crate) fn apply_line_style(content: &str, style: LineStyle) -> String {
+    let ending = style.line_ending.as_str();
+    let mut result = String::with_capacity(content.len() + content.len() / 32);
+
+    for (line, line_break) in split_lines(content) {
+        result.push_str(line);
+        if !line_break.is_empty() {
+            result.push_str(ending);
+        }
+    }
+
+    set_final_newline(&mut result, style.final_newline, ending);
+    result
+}
+
+/// Drop a single trailing line break from `result`, or add `ending` when it
+/// has none, to match `final_newline`. Empty content is left alone.
+fn set_final_newline(result: &mut String, final_newline: bool, ending: &str) {
+    let trailing = if result.ends_with("\r\n") {
+        2
+    } else if result.ends_with(['\r', '\n']) {
+        1
+    } else {
+        0
+    };
+
+    if final_newline {
+        if !result.is_empty() && trailing == 0 {
+            result.push_str(ending);
+        }
+    } else {
+        result.truncate(result.len() - trailing);
+    }
+}
+
+/// Like `apply_line_style`, but lines `content` shares with `stored`, the text
+/// on disk, keep the line break they have there, so saving a file with mixed
+/// line endings only touches the lines that were edited. New and edited lines
+/// get `style`'s ending.
+pub(crate) fn preserve_line_style(content: &str, stored: &str, style: LineStyle) -> String {
+    let old: Vec<(&str, &str)> = split_lines(stored).collect();
+    let new: Vec<(&str, &str)> = split_lines(content).collect();
+    let old_lines: Vec<&str> = old.iter().map(|(line, _)| *line).collect();
+    let new_lines: Vec<&str> = new.iter().map(|(line, _)| *line).collect();
+
+    let mut endings = vec![style.line_ending.as_str(); new.len()];
+    for op in TextDiff::from_slices(&old_lines, &new_lines).ops() {
+        if op.tag() != DiffTag::Equal {
+            continue;
+        }
+
+        for (old_index, new_index) in op.old_range().zip(op.new_range()) {
+            let (_, line_break) = old[old_index];
+            if !line_break.is_empty() {
+                endings[new_index] = line_break;
+            }
+        }
+    }
+
+    let mut result = String::with_capacity(content.len() + content.len() / 32);
+    for ((line, line_break), ending) in new.iter().zip(&endings) {
+        result.push_str(line);
+        if !line_break.is_empty() {
+            result.push_str(ending);
+        }
+    }
+
+    let last_ending = endings.last().copied().unwrap_or(style.line_ending.as_str());
+    set_final_newline(&mut result, style.final_newline, last_ending);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_line_style, detect_line_style, preserve_line_style, same_lines, LineEnding, LineStyle};
+
+    #[test]
+    fn detect_line_style_picks_the_predominant_ending() {
+        assert_eq!(
+            detect_line_style("a\r\nb\r\nc\n"),
+            LineStyle {
+                line_ending: LineEnding::Crlf,
+                final_newline: true,
+            }
+        );
+        assert_eq!(
+            detect_line_style("a\rb"),
+            LineStyle {
+                line_ending: LineEnding::Cr,
+                final_newline: false,
+            }
+        );
+        assert_eq!(detect_line_style("single line").line_ending, LineEnding::Lf);
+    }
+
+    #[test]
+    fn apply_line_style_normalises_endings_and_final_newline() {
+        let crlf = LineStyle {
+            line_ending: LineEnding::Crlf,
+            final_newline: true,
+        };
+        assert_eq!(apply_line_style("# Title\n\nbody\r\nmore", crlf), "# Title\r\n\r\nbody\r\nmore\r\n");
+        assert_eq!(apply_line_style("", crlf), "");
+
+        let no_final = LineStyle {
+            line_ending: LineEnding::Lf,
+            final_newline: false,
+        };
+        assert_eq!(apply_line_style("a\r\nb\r\n", no_final), "a\nb");
+        assert_eq!(apply_line_style("a\n\n", no_final), "a\n");
+    }
+
+    #[test]
+    fn preserve_line_style_keeps_the_endings_of_unchanged_lines() {
+        let stored = "a\r\nb\nc\r\n";
+        let style = detect_line_style(stored);
+
+        assert_eq!(preserve_line_style("a\nb\nnew\nc\n", stored, style), "a\r\nb\nnew\r\nc\r\n");
+        assert_eq!(preserve_line_style("a\nB\nc", stored, style), "a\r\nB\r\nc\r\n");
+        assert_eq!(
+            preserve_line_style(
+                "a\nb",
+                "a\nb",
+                LineStyle {
+                    line_ending: LineEnding::Crlf,
+                    final_newline: false,
+                },
+            ),
+            "a\nb"
+        );
+        assert!(same_lines("a\r\nb", "a\nb\n"));
+        assert!(!same_lines("a\nb", "a\nc"));
+    }
+}
@@ -0,0 +1,567 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::AppHandle;
+use super::encoding::decode_text;
+use super::file::{content_hash, is_markdown_file, save_document, workspace_files, SaveFormat, SaveOutcome};
+use super::line_endings::same_lines;
+use super::paths::IpcPath;
+
+/// A markdown file whose links were rewritten after a rename or move.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LinkUpdate {
+    pub path: IpcPath,
+    pub links: usize,
+}
+
+/// The rewrite of one file's links after a rename or move, kept in the journal
+/// so undo and redo can put the links back. `path` is where the file was right
+/// after the move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkEdit {
+    pub path: IpcPath,
+    pub links: usize,
+    pub before: String,
+    pub after: String,
+}
+
+impl LinkEdit {
+    pub(crate) fn update(&self) -> LinkUpdate {
+        LinkUpdate {
+            path: self.path.clone(),
+            links: self.links,
+        }
+    }
+}
+
+/// Inline links and images: `[text](target "title")` / `![alt](target)`.
+fn inline_link_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r#"(?P<prefix>!?\[(?:[^\[\]\n]|\[[^\]\n]*\])*\]\(\s*)(?P<target><[^>\n]*>|[^\s()]+(?:\([^\s()]*\)[^\s()]*)*)(?P<suffix>(?:\s+(?:"[^"\n]*"|'[^'\n]*'|\([^)\n]*\)))?\s*\))"#,
+        )
+        .expect("inline link pattern should compile")
+    })
+}
+
+/// Reference definitions: `[id]: target "title"`.
+fn reference_link_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"(?m)^(?P<prefix> {0,3}\[[^\]\n]+\]:[ \t]*)(?P<target><[^>\n]*>|\S+)(?P<suffix>)")
+            .expect("reference link pattern should compile")
+    })
+}
+
+/// Whether `line`, without its indentation, starts a list item.
+fn is_list_item(line: &str) -> bool {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let rest = &line[digits..];
+    let marker = match digits {
+        0 => rest.strip_prefix(['-', '*', '+']),
+        _ => rest.strip_prefix(['.', ')']),
+    };
+
+    marker.is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t', '\n', '\r']))
+}
+
+/// Add the inline code spans of one paragraph, found at `offset` in the
+/// document. A run of backticks without a closing run of the same length is
+/// plain text.
+fn push_code_spans(paragraph: &str, offset: usize, ranges: &mut Vec<Range<usize>>) {
+    let bytes = paragraph.as_bytes();
+    let run_at = |index: usize| bytes[index..].iter().take_while(|byte| **byte == b'`').count();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] != b'`' {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        let run = run_at(start);
+        index += run;
+
+        let mut close = index;
+        while close < bytes.len() {
+            if bytes[close] != b'`' {
+                close += 1;
+                continue;
+            }
+
+            let length = run_at(close);
+            if length == run {
+                ranges.push(offset + start..offset + close + length);
+                index = close + length;
+                break;
+            }
+            close += length;
+        }
+    }
+}
+
+/// Byte ranges of code, whose contents are never rewritten: fenced and
+/// indented code blocks, and inline code spans.
+fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut fence: Option<(char, usize, usize)> = None;
+    let mut indented: Option<Range<usize>> = None;
+    let mut paragraph: Option<Range<usize>> = None;
+    let mut in_list = false;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let line_range = offset..offset + line.len();
+        offset += line.len();
+
+        let trimmed = line.trim_start_matches(' ');
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let run = marker.map_or(0, |c| trimmed.chars().take_while(|x| *x == c).count());
+
+        if let Some((c, len, start)) = fence {
+            if marker == Some(c) && run >= len && trimmed[run..].trim().is_empty() {
+                ranges.push(start..line_range.end);
+                fence = None;
+            }
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            if let Some(text) = paragraph.take() {
+                push_code_spans(&content[text.clone()], text.start, &mut ranges);
+            }
+            continue;
+        }
+
+        let is_indented = line.starts_with("    ") || line.starts_with('\t');
+        // Indented code cannot interrupt a paragraph, and inside a list it is
+        // the item's own content.
+        if is_indented && paragraph.is_none() && !in_list {
+            let start = indented.as_ref().map_or(line_range.start, |block| block.start);
+            indented = Some(start..line_range.end);
+            continue;
+        }
+
+        ranges.extend(indented.take());
+
+        if run >= 3 && line.len() - trimmed.len() < 4 {
+            if let Some(text) = paragraph.take() {
+                push_code_spans(&content[text.clone()], text.start, &mut ranges);
+            }
+            fence = marker.map(|c| (c, run, line_range.start));
+            continue;
+        }
+
+        if !is_indented {
+            in_list = is_list_item(trimmed);
+        }
+        let start = paragraph.as_ref().map_or(line_range.start, |text| text.start);
+        paragraph = Some(start..line_range.end);
+    }
+
+    if let Some((_, _, start)) = fence {
+        ranges.push(start..content.len());
+    }
+    ranges.extend(indented);
+    if let Some(text) = paragraph {
+        push_code_spans(&content[text.clone()], text.start, &mut ranges);
+    }
+
+    ranges
+}
+
+/// Collapse `.` and `..` components without touching the filesystem.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
+}
+
+/// Relative link from `from_dir` to `to`, always using forward slashes.
+fn relative_link(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to_components: Vec<Component> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(&to_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to_components[common..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned()),
+    );
+
+    parts.join("/")
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|pair| std::str::from_utf8(pair).ok())
+            .and_then(|pair| u8::from_str_radix(pair, 16).ok());
+
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            ' ' => encoded.push_str("%20"),
+            '%' => encoded.push_str("%25"),
+            '(' => encoded.push_str("%28"),
+            ')' => encoded.push_str("%29"),
+            '<' => encoded.push_str("%3C"),
+            '>' => encoded.push_str("%3E"),
+            c => encoded.push(c),
+        }
+    }
+
+    encoded
+}
+
+/// Where `path` lives after `moved_from` was moved to `moved_to`.
+fn remap(path: &Path, moved_from: &Path, moved_to: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(moved_from).ok()?;
+    if relative.as_os_str().is_empty() {
+        Some(moved_to.to_path_buf())
+    } else {
+        Some(moved_to.join(relative))
+    }
+}
+
+/// Rewrite a single link target, or return `None` when it should stay as is.
+fn rewrite_target(
+    raw: &str,
+    old_dir: &Path,
+    new_dir: &Path,
+    moved_from: &Path,
+    moved_to: &Path,
+) -> Option<String> {
+    let (inner, angled) = match raw.strip_prefix('<').and_then(|t| t.strip_suffix('>')) {
+        Some(inner) => (inner, true),
+        None => (raw, false),
+    };
+
+    let split = inner.find(['#', '?']).unwrap_or(inner.len());
+    let (path_part, fragment) = inner.split_at(split);
+
+    let is_external = path_part.starts_with('/')
+        || path_part
+            .split_once(':')
+            .is_some_and(|(scheme, _)| !scheme.contains(['/', '\\', '.']));
+    if path_part.is_empty() || is_external {
+        return None;
+    }
+
+    let target = normalize_path(&old_dir.join(percent_decode(path_part)));
+    let remapped = remap(&target, moved_from, moved_to);
+    if remapped.is_none() && old_dir == new_dir {
+        return None;
+    }
+
+    let mut link = relative_link(new_dir, &remapped.unwrap_or(target));
+    if path_part.starts_with("./") && !link.starts_with("..") {
+        link.insert_str(0, "./");
+    }
+    if !angled {
+        link = percent_encode(&link);
+    }
+    if link == path_part {
+        return None;
+    }
+
+    Some(if angled {
+        format!("<{}{}>", link, fragment)
+    } else {
+        format!("{}{}", link, fragment)
+    })
+}
+
+/// Rewrite every relative link in `content` that was affected by moving
+/// `moved_from` to `moved_to`. `file_old` and `file_new` are the document's own
+/// location before and after the move. Returns the new content and the number
+/// of links changed.
+fn rewrite_links(
+    content: &str,
+    file_old: &Path,
+    file_new: &Path,
+    moved_from: &Path,
+    moved_to: &Path,
+) -> (String, usize) {
+    let old_dir = file_old.parent().unwrap_or(Path::new(""));
+    let new_dir = file_new.parent().unwrap_or(Path::new(""));
+    let mut rewritten = content.to_string();
+    let mut changed = 0;
+
+    for regex in [inline_link_regex(), reference_link_regex()] {
+        let code_ranges = code_ranges(&rewritten);
+
+        rewritten = regex
+            .replace_all(&rewritten, |caps: &Captures| {
+                let whole = caps.get(0).expect("match should have a whole group");
+                if code_ranges.iter().any(|range| range.contains(&whole.start())) {
+                    return whole.as_str().to_string();
+                }
+
+                match rewrite_target(&caps["target"], old_dir, new_dir, moved_from, moved_to) {
+                    Some(target) => {
+                        changed += 1;
+                        format!("{}{}{}", &caps["prefix"], target, &caps["suffix"])
+                    }
+                    None => whole.as_str().to_string(),
+                }
+            })
+            .into_owned();
+    }
+
+    (rewritten, changed)
+}
+
+/// Where `path`, after `moved_from` was moved to `moved_to`, was before the move.
+fn path_before_move(path: &Path, moved_from: &Path, moved_to: &Path) -> PathBuf {
+    match path.strip_prefix(moved_to) {
+        Ok(relative) if relative.as_os_str().is_empty() => moved_from.to_path_buf(),
+        Ok(relative) => moved_from.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// The link rewrites the workspace's markdown files need after `moved_from`
+/// was renamed or moved to `moved_to`, each with the hash of the file as read.
+/// Files in legacy encodings are decoded; unreadable files are skipped.
+fn plan_link_edits(root: &Path, moved_from: &Path, moved_to: &Path) -> Vec<(LinkEdit, String)> {
+    let mut edits = Vec::new();
+
+    for file_new in workspace_files(root) {
+        if !is_markdown_file(&file_new) {
+            continue;
+        }
+
+        let Ok(bytes) = fs::read(&file_new) else {
+            continue;
+        };
+        let (content, _) = decode_text(&bytes);
+
+        let file_old = path_before_move(&file_new, moved_from, moved_to);
+        let (rewritten, links) = rewrite_links(&content, &file_old, &file_new, moved_from, moved_to);
+        if links == 0 {
+            continue;
+        }
+
+        edits.push((
+            LinkEdit {
+                path: IpcPath::from(&file_new),
+                links,
+                before: content,
+                after: rewritten,
+            },
+            content_hash(&bytes),
+        ));
+    }
+
+    edits
+}
+
+/// Replace the text of `path` with `replacement` through the editor's save path,
+/// provided it still reads as `expected`. Returns the text as written.
+fn replace_document(app: &AppHandle, path: &Path, expected: &str, replacement: &str) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    if !same_lines(&decode_text(&bytes).0, expected) {
+        return None;
+    }
+
+    let hash = content_hash(&bytes);
+    match save_document(app, path, replacement, None, Some(&hash), SaveFormat::default(), false) {
+        Ok((SaveOutcome::Saved { .. }, written)) => Some(written),
+        _ => None,
+    }
+}
+
+/// After `moved_from` was renamed or moved to `moved_to`, rewrite relative links
+/// across the workspace's markdown files so they keep pointing at the same
+/// documents. Files are saved like editor saves, keeping their encoding and
+/// getting a backup and history entry; files that cannot be read, or that
+/// change while being rewritten, are left untouched.
+pub(crate) fn update_links_after_move(
+    app: &AppHandle,
+    root: &Path,
+    moved_from: &Path,
+    moved_to: &Path,
+) -> Vec<LinkEdit> {
+    plan_link_edits(root, moved_from, moved_to)
+        .into_iter()
+        .filter_map(|(mut edit, hash)| {
+            let path = edit.path.to_path_buf();
+            let saved = save_document(app, &path, &edit.after, None, Some(&hash), SaveFormat::default(), false);
+            match saved {
+                Ok((SaveOutcome::Saved { .. }, written)) => {
+                    edit.after = written;
+                    Some(edit)
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Put back the links `edits` rewrote, once the item has moved back from
+/// `moved_to` to `moved_from`. Files edited since the rewrite are left alone.
+pub(crate) fn revert_link_edits(app: &AppHandle, edits: &[LinkEdit], moved_from: &Path, moved_to: &Path) {
+    for edit in edits {
+        let path = path_before_move(&edit.path.to_path_buf(), moved_from, moved_to);
+        replace_document(app, &path, &edit.after, &edit.before);
+    }
+}
+
+/// Rewrite the links in `edits` again after the move is redone. Files edited
+/// since the rewrite was undone are left alone.
+pub(crate) fn reapply_link_edits(app: &AppHandle, edits: &[LinkEdit]) {
+    for edit in edits {
+        replace_document(app, &edit.path.to_path_buf(), &edit.before, &edit.after);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plan_link_edits, rewrite_links};
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn rewrite_links_follows_renamed_targets_and_keeps_fragments() {
+        let content = "See [a](notes/a.md#intro \"Title\") and ![img](<notes/a.md>).\n\
+                       [ref]: ./notes/a.md\n\
+                       [web](https://example.com/notes/a.md) [other](notes/b.md)\n\
+                       ```\n[code](notes/a.md)\n```\n";
+
+        let (rewritten, changed) = rewrite_links(
+            content,
+            Path::new("/ws/index.md"),
+            Path::new("/ws/index.md"),
+            Path::new("/ws/notes/a.md"),
+            Path::new("/ws/notes/renamed a.md"),
+        );
+
+        assert_eq!(changed, 3);
+        assert!(rewritten.contains("[a](notes/renamed%20a.md#intro \"Title\")"));
+        assert!(rewritten.contains("![img](<notes/renamed a.md>)"));
+        assert!(rewritten.contains("[ref]: ./notes/renamed%20a.md"));
+        assert!(rewritten.contains("[web](https://example.com/notes/a.md)"));
+        assert!(rewritten.contains("[other](notes/b.md)"));
+        assert!(rewritten.contains("[code](notes/a.md)"));
+    }
+
+    #[test]
+    fn rewrite_links_leaves_code_spans_and_indented_code_alone() {
+        let content = "Write `[x](notes/a.md)` or ``[y](`notes/a.md`)``, but [z](notes/a.md).\n\
+                       A stray ` backtick and [w](notes/a.md).\n\
+                       \n    [code](notes/a.md)\n\
+                       \n- item\n\n    [nested](notes/a.md)\n";
+
+        let (rewritten, changed) = rewrite_links(
+            content,
+            Path::new("/ws/index.md"),
+            Path::new("/ws/index.md"),
+            Path::new("/ws/notes/a.md"),
+            Path::new("/ws/notes/b.md"),
+        );
+
+        assert_eq!(changed, 3);
+        assert!(rewritten.contains("`[x](notes/a.md)`"));
+        assert!(rewritten.contains("``[y](`notes/a.md`)``"));
+        assert!(rewritten.contains("[z](notes/b.md)"));
+        assert!(rewritten.contains("[w](notes/b.md)"));
+        assert!(rewritten.contains("    [code](notes/a.md)"));
+        assert!(rewritten.contains("    [nested](notes/b.md)"));
+    }
+
+    #[test]
+    fn rewrite_links_updates_links_inside_moved_files() {
+        let (rewritten, changed) = rewrite_links(
+            "[sibling](b.md) [up](../index.md) [self](#top)",
+            Path::new("/ws/notes/a.md"),
+            Path::new("/ws/archive/2024/a.md"),
+            Path::new("/ws/notes/a.md"),
+            Path::new("/ws/archive/2024/a.md"),
+        );
+
+        assert_eq!(changed, 2);
+        assert_eq!(rewritten, "[sibling](../../notes/b.md) [up](../../index.md) [self](#top)");
+    }
+
+    #[test]
+    fn plan_link_edits_covers_moved_and_legacy_encoded_files() {
+        let root = make_temp_dir("links-move");
+        fs::create_dir_all(root.join("archive")).expect("failed to create archive folder");
+        fs::write(root.join("index.md"), "[note](note.md)").expect("failed to write index");
+        fs::write(root.join("archive").join("note.md"), "[home](index.md)")
+            .expect("failed to write moved note");
+        fs::write(root.join("other.md"), "[web](https://example.com)").expect("failed to write other");
+        // "café" in Windows-1252, which is not valid UTF-8.
+        fs::write(root.join("legacy.md"), b"caf\xe9 [note](note.md)").expect("failed to write legacy note");
+
+        let mut edits = plan_link_edits(
+            &root,
+            &root.join("note.md"),
+            &root.join("archive").join("note.md"),
+        );
+        edits.sort_by_key(|(edit, _)| edit.path.to_path_buf());
+
+        let rewritten: Vec<(String, &str)> = edits
+            .iter()
+            .map(|(edit, _)| {
+                let name = edit.path.to_path_buf().file_name().map(|name| name.to_string_lossy().into_owned());
+                (name.unwrap_or_default(), edit.after.as_str())
+            })
+            .collect();
+        assert_eq!(
+            rewritten,
+            vec![
+                ("note.md".to_string(), "[home](../index.md)"),
+                ("index.md".to_string(), "[note](archive/note.md)"),
+                ("legacy.md".to_string(), "caf\u{e9} [note](archive/note.md)"),
+            ]
+        );
+        assert_eq!(edits[1].0.before, "[note](note.md)");
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -1 +1,19 @@
-pub mod file;
\ No newline at end of file
+pub mod backup;
+pub mod encoding;
+pub mod error;
+pub mod file;
+pub mod history;
+pub mod ignore;
+pub mod index;
+pub mod journal;
+pub mod line_endings;
+pub mod links;
+pub mod paths;
+pub mod quick_open;
+pub mod recovery;
+pub mod sandbox;
+pub mod search;
+pub mod settings;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod watch;
@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// A path as it crosses the IPC boundary.
+///
+/// Paths that are valid UTF-8 travel as plain strings. Anything else is sent
+/// as its raw bytes together with a lossy `display` form, so the frontend can
+/// show the name and hand the exact path back. On Windows the bytes are the
+/// UTF-16 code units in little-endian order.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum IpcPath {
+    Text(String),
+    Raw { display: String, bytes: Vec<u8> },
+}
+
+#[cfg(unix)]
+fn os_string_to_bytes(value: &std::ffi::OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    value.as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes.to_vec())
+}
+
+#[cfg(windows)]
+fn os_string_to_bytes(value: &std::ffi::OsStr) -> Vec<u8> {
+    use std::os::windows::ffi::OsStrExt;
+    value.encode_wide().flat_map(u16::to_le_bytes).collect()
+}
+
+#[cfg(windows)]
+fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+    use std::os::windows::ffi::OsStringExt;
+    let wide: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    OsString::from_wide(&wide)
+}
+
+impl IpcPath {
+    /// The filesystem path this value names.
+    pub fn to_path_buf(&self) -> PathBuf {
+        match self {
+            IpcPath::Text(text) => PathBuf::from(text),
+            IpcPath::Raw { bytes, .. } => PathBuf::from(os_string_from_bytes(bytes)),
+        }
+    }
+
+    /// A readable form of the path, with undecodable parts replaced.
+    pub fn display(&self) -> &str {
+        match self {
+            IpcPath::Text(text) => text,
+            IpcPath::Raw { display, .. } => display,
+        }
+    }
+}
+
+impl From<&Path> for IpcPath {
+    fn from(path: &Path) -> Self {
+        match path.to_str() {
+            Some(text) => IpcPath::Text(text.to_string()),
+            None => IpcPath::Raw {
+                display: path.to_string_lossy().into_owned(),
+                bytes: os_string_to_bytes(path.as_os_str()),
+            },
+        }
+    }
+}
+
+impl From<PathBuf> for IpcPath {
+    fn from(path: PathBuf) -> Self {
+        IpcPath::from(path.as_path())
+    }
+}
+
+impl From<&PathBuf> for IpcPath {
+    fn from(path: &PathBuf) -> Self {
+        IpcPath::from(path.as_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IpcPath;
+    use std::path::PathBuf;
+
+    #[test]
+    fn utf8_paths_travel_as_plain_strings() {
+        let path = IpcPath::from(PathBuf::from("/notes/kea.md"));
+
+        assert_eq!(path, IpcPath::Text("/notes/kea.md".to_string()));
+        assert_eq!(serde_json::to_string(&path).expect("path should serialize"), "\"/notes/kea.md\"");
+
+        let parsed: IpcPath = serde_json::from_str("\"/notes/kea.md\"").expect("string should parse");
+        assert_eq!(parsed.to_path_buf(), PathBuf::from("/notes/kea.md"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_round_trip_through_json() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let original = PathBuf::from(OsString::from_vec(b"/notes/caf\xe9.md".to_vec()));
+        let path = IpcPath::from(&original);
+        assert_eq!(path.display(), "/notes/caf\u{fffd}.md");
+
+        let json = serde_json::to_string(&path).expect("path should serialize");
+        let parsed: IpcPath = serde_json::from_str(&json).expect("raw path should parse");
+        assert_eq!(parsed.to_path_buf(), original);
+    }
+}
@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::State;
+use super::error::CommandError;
+use super::file::{is_markdown_file, workspace_files};
+use super::paths::IpcPath;
+use super::sandbox::check_access;
+use crate::{QuickOpenRegistry, WorkspaceAccess};
+
+/// Results returned when the caller does not ask for a limit.
+const DEFAULT_RESULT_LIMIT: usize = 20;
+
+/// Score for every matched character.
+const MATCH_SCORE: i64 = 16;
+/// Extra score when a character directly follows the previous match.
+const CONSECUTIVE_BONUS: i64 = 16;
+/// Extra score for matching the first character of a path segment.
+const SEGMENT_START_BONUS: i64 = 24;
+/// Extra score for matching the first character of a word inside a segment.
+const WORD_START_BONUS: i64 = 12;
+/// Extra score for characters matched in the file name rather than its folders.
+const FILE_NAME_BONUS: i64 = 8;
+/// Extra score when the file name starts with the query.
+const FILE_NAME_PREFIX_BONUS: i64 = 48;
+/// Penalty per character skipped between two matches.
+const GAP_PENALTY: i64 = 1;
+/// Penalty per folder level, so shallower paths win ties.
+const DEPTH_PENALTY: i64 = 2;
+/// Boost for the most recently opened file; later entries get a fraction of it.
+const RECENCY_BONUS: i64 = 64;
+
+/// A cached workspace file, with its path relative to the root using `/`.
+#[derive(Debug, Clone)]
+pub struct QuickOpenEntry {
+    path: IpcPath,
+    relative: Vec<char>,
+    name_start: usize,
+}
+
+/// A ranked Quick Open result. `positions` are character indices into
+/// `relative_path` to highlight.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QuickOpenMatch {
+    pub path: IpcPath,
+    pub name: String,
+    pub relative_path: String,
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+impl QuickOpenEntry {
+    fn new(root: &Path, path: &Path) -> Option<Self> {
+        let relative = path.strip_prefix(root).ok()?;
+        let relative: String = relative
+            .iter()
+            .map(|part| part.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        let relative: Vec<char> = relative.chars().collect();
+        let name_start = relative
+            .iter()
+            .rposition(|c| *c == '/')
+            .map_or(0, |slash| slash + 1);
+
+        Some(Self {
+            path: IpcPath::from(path),
+            relative,
+            name_start,
+        })
+    }
+
+    fn to_match(&self, score: i64, positions: Vec<usize>) -> QuickOpenMatch {
+        QuickOpenMatch {
+            path: self.path.clone(),
+            name: self.relative[self.name_start..].iter().collect(),
+            relative_path: self.relative.iter().collect(),
+            score,
+            positions,
+        }
+    }
+}
+
+/// Every markdown file in the workspace, sorted by relative path.
+fn collect_entries(root: &Path) -> Vec<QuickOpenEntry> {
+    let mut entries: Vec<QuickOpenEntry> = workspace_files(root)
+        .iter()
+        .filter(|path| is_markdown_file(path))
+        .filter_map(|path| QuickOpenEntry::new(root, path))
+        .collect();
+
+    entries.sort_by(|a, b| a.relative.cmp(&b.relative));
+    entries
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Bonus for matching the character at `index`, based on what precedes it.
+fn position_bonus(text: &[char], index: usize, name_start: usize) -> i64 {
+    let boundary = match index.checked_sub(1).map(|prev| text[prev]) {
+        None | Some('/') => SEGMENT_START_BONUS,
+        Some('-' | '_' | ' ' | '.') => WORD_START_BONUS,
+        Some(prev) if prev.is_lowercase() && text[index].is_uppercase() => WORD_START_BONUS,
+        Some(_) => 0,
+    };
+
+    if index >= name_start {
+        boundary + FILE_NAME_BONUS
+    } else {
+        boundary
+    }
+}
+
+/// Best alignment of `query` (already lowercased) as a subsequence of `text`.
+///
+/// Returns the score and the matched character positions, or `None` when the
+/// query is not a subsequence of the text.
+fn fuzzy_match(query: &[char], text: &[char], name_start: usize) -> Option<(i64, Vec<usize>)> {
+    let lower: Vec<char> = text.iter().map(|c| lowercase(*c)).collect();
+
+    let mut remaining = query.iter().peekable();
+    for c in &lower {
+        if remaining.peek() == Some(&c) {
+            remaining.next();
+        }
+    }
+    if remaining.peek().is_some() {
+        return None;
+    }
+
+    // scores[i][j]: best score with query[i] matched at text[j]; from[i][j]: where query[i - 1] matched.
+    let mut scores: Vec<Vec<Option<i64>>> = vec![vec![None; lower.len()]; query.len()];
+    let mut from: Vec<Vec<usize>> = vec![vec![0; lower.len()]; query.len()];
+
+    for (i, expected) in query.iter().enumerate() {
+        // Best score of query[i - 1] matched at least two characters back, less the gap.
+        let mut gapped: Option<(i64, usize)> = None;
+
+        for j in 0..lower.len() {
+            if i > 0 {
+                gapped = gapped.map(|(score, k)| (score - GAP_PENALTY, k));
+                if let Some(score) = j.checked_sub(2).and_then(|k| scores[i - 1][k]) {
+                    let candidate = score - GAP_PENALTY;
+                    if gapped.map_or(true, |(best, _)| candidate > best) {
+                        gapped = Some((candidate, j - 2));
+                    }
+                }
+            }
+
+            if lower[j] != *expected {
+                continue;
+            }
+
+            let bonus = MATCH_SCORE + position_bonus(text, j, name_start);
+
+            if i == 0 {
+                scores[i][j] = Some(bonus);
+                continue;
+            }
+
+            let consecutive = j
+                .checked_sub(1)
+                .and_then(|k| scores[i - 1][k].map(|score| (score + CONSECUTIVE_BONUS, k)));
+
+            let best = match (consecutive, gapped) {
+                (Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+                (a, b) => a.or(b),
+            };
+
+            if let Some((score, k)) = best {
+                scores[i][j] = Some(score + bonus);
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let last = query.len() - 1;
+    let (mut position, score) = scores[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| score.map(|score| (j, score)))
+        .max_by_key(|(j, score)| (*score, std::cmp::Reverse(*j)))?;
+
+    let mut positions = vec![position];
+    for i in (1..query.len()).rev() {
+        position = from[i][position];
+        positions.push(position);
+    }
+    positions.reverse();
+
+    Some((score, positions))
+}
+
+/// Rank `entries` against `query`, boosting files in `recent` (most recent first).
+fn rank_entries(
+    entries: &[QuickOpenEntry],
+    query: &str,
+    recent: &[IpcPath],
+    limit: usize,
+) -> Vec<QuickOpenMatch> {
+    let recency = |entry: &QuickOpenEntry| {
+        recent
+            .iter()
+            .position(|path| *path == entry.path)
+            .map_or(0, |rank| RECENCY_BONUS / (rank as i64 + 1))
+    };
+
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lowercase)
+        .collect();
+
+    let mut matches: Vec<QuickOpenMatch> = if query.is_empty() {
+        entries
+            .iter()
+            .map(|entry| entry.to_match(recency(entry), Vec::new()))
+            .collect()
+    } else {
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let (mut score, positions) = fuzzy_match(&query, &entry.relative, entry.name_start)?;
+
+                let name = entry.relative[entry.name_start..].iter().map(|c| lowercase(*c));
+                if name.take(query.len()).eq(query.iter().copied()) {
+                    score += FILE_NAME_PREFIX_BONUS;
+                }
+
+                let depth = entry.relative.iter().filter(|c| **c == '/').count() as i64;
+                score += recency(entry) - depth * DEPTH_PENALTY;
+
+                Some(entry.to_match(score, positions))
+            })
+            .collect()
+    };
+
+    matches.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.relative_path.cmp(&b.relative_path))
+    });
+    matches.truncate(limit);
+
+    matches
+}
+
+/// Forget the cached path list of a workspace so the next query rebuilds it.
+pub(crate) fn invalidate_quick_open(registry: &QuickOpenRegistry, root: &Path) {
+    if let Ok(mut workspaces) = registry.workspaces.lock() {
+        workspaces.remove(root);
+    }
+}
+
+/// Forget the cached path lists of every workspace containing `path`, after a
+/// command created, moved or deleted it there. The watcher would catch up too,
+/// but only after its debounce delay and never for folders it ignores.
+pub(crate) fn invalidate_quick_open_containing(registry: &QuickOpenRegistry, path: &Path) {
+    if let Ok(mut workspaces) = registry.workspaces.lock() {
+        workspaces.retain(|root, _| !path.starts_with(root));
+    }
+}
+
+/// Fuzzy-match markdown files anywhere in the workspace for Quick Open.
+///
+/// The workspace's path list is cached after the first query and refreshed
+/// when file commands or the workspace watcher report tree changes. `recent` lists recently
+/// opened paths, most recent first, which are ranked higher.
+#[tauri::command]
+pub async fn quick_open_query(
+    access: State<'_, WorkspaceAccess>,
+    state: State<'_, QuickOpenRegistry>,
+    root: IpcPath,
+    query: String,
+    recent: Option<Vec<IpcPath>>,
+    limit: Option<usize>,
+) -> Result<Vec<QuickOpenMatch>, CommandError> {
+    let root = root.to_path_buf();
+    check_access(&access, &root)?;
+    if !root.is_dir() {
+        return Err(CommandError::Other(format!("{} is not a directory", root.display())));
+    }
+
+    let mut workspaces = state
+        .workspaces
+        .lock()
+        .map_err(|_| "Failed to lock quick open cache")?;
+
+    let entries = workspaces
+        .entry(root.clone())
+        .or_insert_with(|| collect_entries(&root));
+
+    Ok(rank_entries(
+        entries,
+        &query,
+        &recent.unwrap_or_default(),
+        limit.unwrap_or(DEFAULT_RESULT_LIMIT),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_entries, fuzzy_match, invalidate_quick_open_containing, rank_entries};
+    use crate::commands::paths::IpcPath;
+    use crate::commands::test_support::make_temp_dir;
+    use crate::QuickOpenRegistry;
+    use std::fs;
+
+    #[test]
+    fn fuzzy_match_prefers_segment_starts_and_reports_positions() {
+        let text: Vec<char> = "notes/meeting-notes.md".chars().collect();
+        let query: Vec<char> = "mn".chars().collect();
+
+        let (_, positions) = fuzzy_match(&query, &text, 6).expect("query should match");
+        assert_eq!(positions, vec![6, 14]);
+
+        let missing: Vec<char> = "zz".chars().collect();
+        assert!(fuzzy_match(&missing, &text, 6).is_none());
+    }
+
+    #[test]
+    fn rank_entries_scores_file_names_and_boosts_recent_files() {
+        let root = make_temp_dir("quick-open-rank");
+        fs::create_dir_all(root.join("projects").join("kea")).expect("failed to create folders");
+        fs::write(root.join("projects").join("kea").join("roadmap.md"), "").expect("failed to write note");
+        fs::write(root.join("kea.md"), "").expect("failed to write note");
+        fs::write(root.join("kettle.md"), "").expect("failed to write note");
+        fs::write(root.join("image.png"), "").expect("failed to write image");
+
+        let entries = collect_entries(&root);
+        assert_eq!(entries.len(), 3);
+
+        let ranked = rank_entries(&entries, "kea", &[], 10);
+        let relative: Vec<&str> = ranked.iter().map(|hit| hit.relative_path.as_str()).collect();
+        assert_eq!(relative, vec!["kea.md", "projects/kea/roadmap.md"]);
+        assert_eq!(ranked[0].positions, vec![0, 1, 2]);
+        assert_eq!(ranked[1].positions, vec![9, 10, 11]);
+
+        let recent = vec![IpcPath::from(root.join("kettle.md"))];
+        let browsing = rank_entries(&entries, "", &recent, 2);
+        assert_eq!(browsing[0].name, "kettle.md");
+        assert_eq!(browsing[1].name, "kea.md");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn invalidation_drops_only_workspaces_containing_the_path() {
+        let registry = QuickOpenRegistry::default();
+        let root = make_temp_dir("quick-open-invalidate");
+        let notes = root.join("notes");
+        let other = root.join("other");
+        {
+            let mut workspaces = registry.workspaces.lock().expect("cache should lock");
+            workspaces.insert(notes.clone(), Vec::new());
+            workspaces.insert(other.clone(), Vec::new());
+        }
+
+        invalidate_quick_open_containing(&registry, &notes.join("new.md"));
+
+        let workspaces = registry.workspaces.lock().expect("cache should lock");
+        assert!(!workspaces.contains_key(&notes));
+        assert!(workspaces.contains_key(&other));
+    }
+}
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+use super::error::CommandError;
+use super::file::{atomic_write_file, content_hash};
+use super::paths::IpcPath;
+use super::sandbox::check_access;
+use crate::{RecoveryJournal, WorkspaceAccess};
+
+/// The journal is rewritten with only the live buffers once it grows past this.
+const MAX_JOURNAL_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Latest unsaved state of an editor buffer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BufferState {
+    /// Frontend identifier of the document.
+    pub buffer_id: String,
+    /// File the buffer edits, `None` for an untitled document.
+    pub path: Option<IpcPath>,
+    /// Hash of the file content the edits started from.
+    pub base_hash: Option<String>,
+    pub content: String,
+    /// When the state was journaled, in milliseconds since the Unix epoch.
+    pub updated: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum JournalRecord {
+    Update(BufferState),
+    Discard { buffer_id: String },
+}
+
+/// A buffer left unsaved by an earlier session.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoverableBuffer {
+    #[serde(flatten)]
+    pub state: BufferState,
+    /// Hash of the file as it is on disk now, `None` when it is missing or
+    /// cannot be read. A value other than `base_hash` means the file changed
+    /// since the edits began.
+    pub current_hash: Option<String>,
+}
+
+/// Append-only log of buffer states, replayed on launch.
+///
+/// Each line is one JSON record; a line torn by a crash is skipped on replay.
+pub struct BufferJournal {
+    path: PathBuf,
+    file: fs::File,
+    length: u64,
+    buffers: HashMap<String, BufferState>,
+    /// Buffers found on disk when the journal was opened and not touched since.
+    recovered: HashSet<String>,
+}
+
+impl BufferJournal {
+    fn open(path: PathBuf) -> Result<Self, CommandError> {
+        let mut buffers = HashMap::new();
+
+        if let Ok(file) = fs::File::open(&path) {
+            for line in BufReader::new(file).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match serde_json::from_str::<JournalRecord>(&line) {
+                    Ok(JournalRecord::Update(state)) => {
+                        buffers.insert(state.buffer_id.clone(), state);
+                    }
+                    Ok(JournalRecord::Discard { buffer_id }) => {
+                        buffers.remove(&buffer_id);
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        let recovered = buffers.keys().cloned().collect();
+        let mut journal = Self {
+            file: Self::compact(&path, &buffers)?,
+            path,
+            length: 0,
+            buffers,
+            recovered,
+        };
+        journal.length = journal.file.metadata().map_or(0, |metadata| metadata.len());
+
+        Ok(journal)
+    }
+
+    /// Rewrite the journal at `path` with just `buffers`, returning a handle to append to it.
+    fn compact(path: &Path, buffers: &HashMap<String, BufferState>) -> Result<fs::File, CommandError> {
+        let mut lines = String::new();
+        for state in buffers.values() {
+            let record = serde_json::to_string(&JournalRecord::Update(state.clone()))
+                .map_err(|e| format!("Failed to serialize buffer: {}", e))?;
+            lines.push_str(&record);
+            lines.push('\n');
+        }
+
+        atomic_write_file(path, lines)?;
+
+        OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| CommandError::io(path, "Failed to open recovery journal", e))
+    }
+
+    fn append(&mut self, record: &JournalRecord) -> Result<(), CommandError> {
+        let mut line = serde_json::to_string(record)
+            .map_err(|e| format!("Failed to serialize buffer: {}", e))?;
+        line.push('\n');
+
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| CommandError::io(&self.path, "Failed to write recovery journal", e))?;
+        self.length += line.len() as u64;
+
+        if self.length > MAX_JOURNAL_BYTES {
+            self.file = Self::compact(&self.path, &self.buffers)?;
+            self.length = self.file.metadata().map_or(0, |metadata| metadata.len());
+        }
+
+        Ok(())
+    }
+
+    fn update(&mut self, state: BufferState) -> Result<(), CommandError> {
+        self.recovered.remove(&state.buffer_id);
+        self.buffers.insert(state.buffer_id.clone(), state.clone());
+        self.append(&JournalRecord::Update(state))
+    }
+
+    fn discard(&mut self, buffer_id: &str) -> Result<(), CommandError> {
+        self.recovered.remove(buffer_id);
+        if self.buffers.remove(buffer_id).is_none() {
+            return Ok(());
+        }
+
+        self.append(&JournalRecord::Discard {
+            buffer_id: buffer_id.to_string(),
+        })
+    }
+
+    fn recovered(&self) -> Vec<BufferState> {
+        let mut states: Vec<BufferState> = self
+            .recovered
+            .iter()
+            .filter_map(|buffer_id| self.buffers.get(buffer_id).cloned())
+            .collect();
+        states.sort_by_key(|state| std::cmp::Reverse(state.updated));
+        states
+    }
+}
+
+/// Run `action` on the journal, opening it on first use.
+fn with_journal<T>(
+    app: &AppHandle,
+    state: &RecoveryJournal,
+    action: impl FnOnce(&mut BufferJournal) -> Result<T, CommandError>,
+) -> Result<T, CommandError> {
+    let mut journal = state
+        .journal
+        .lock()
+        .map_err(|_| "Failed to lock recovery journal")?;
+
+    if journal.is_none() {
+        let data_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+        *journal = Some(BufferJournal::open(data_dir.join("recovery").join("buffers.jsonl"))?);
+    }
+
+    match journal.as_mut() {
+        Some(journal) => action(journal),
+        None => Err(CommandError::Other("Recovery journal is not open".to_string())),
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// Journal the current content of a dirty buffer so it survives a crash.
+/// `base_hash` is the hash of the file content the edits started from.
+#[tauri::command]
+pub async fn journal_buffer(
+    app: AppHandle,
+    state: State<'_, RecoveryJournal>,
+    buffer_id: String,
+    path: Option<IpcPath>,
+    base_hash: Option<String>,
+    content: String,
+) -> Result<(), CommandError> {
+    with_journal(&app, &state, |journal| {
+        journal.update(BufferState {
+            buffer_id,
+            path,
+            base_hash,
+            content,
+            updated: now_millis(),
+        })
+    })
+}
+
+/// Drop a buffer from the journal, once it is saved or closed, or when the user
+/// discards a recovered buffer.
+#[tauri::command]
+pub async fn discard_buffer(
+    app: AppHandle,
+    state: State<'_, RecoveryJournal>,
+    buffer_id: String,
+) -> Result<(), CommandError> {
+    with_journal(&app, &state, |journal| journal.discard(&buffer_id))
+}
+
+/// Buffers a previous session left unsaved, most recently edited first.
+#[tauri::command]
+pub async fn list_recoverable_buffers(
+    app: AppHandle,
+    state: State<'_, RecoveryJournal>,
+    access: State<'_, WorkspaceAccess>,
+) -> Result<Vec<RecoverableBuffer>, CommandError> {
+    let states = with_journal(&app, &state, |journal| Ok(journal.recovered()))?;
+
+    Ok(states
+        .into_iter()
+        .map(|state| {
+            let current_hash = state.path.as_ref().and_then(|path| {
+                let path = path.to_path_buf();
+                check_access(&access, &path).ok()?;
+                fs::read(&path).ok().map(|bytes| content_hash(&bytes))
+            });
+
+            RecoverableBuffer {
+                state,
+                current_hash,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferJournal, BufferState};
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    fn buffer(buffer_id: &str, content: &str, updated: u64) -> BufferState {
+        BufferState {
+            buffer_id: buffer_id.to_string(),
+            path: None,
+            base_hash: Some("base".to_string()),
+            content: content.to_string(),
+            updated,
+        }
+    }
+
+    #[test]
+    fn buffer_journal_replays_latest_states_and_skips_torn_lines() {
+        let dir = make_temp_dir("recovery-replay");
+        let path = dir.join("buffers.jsonl");
+
+        let mut journal = BufferJournal::open(path.clone()).expect("journal should open");
+        assert!(journal.recovered().is_empty());
+        journal.update(buffer("a", "draft 1", 1)).expect("update should be journaled");
+        journal.update(buffer("a", "draft 2", 2)).expect("update should be journaled");
+        journal.update(buffer("b", "saved later", 3)).expect("update should be journaled");
+        journal.discard("b").expect("discard should be journaled");
+        drop(journal);
+
+        // Simulate a crash in the middle of an append.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(b"{\"op\":\"update\",\"buffer_id\":\"c\",\"con"))
+            .expect("failed to tear the journal");
+
+        let mut journal = BufferJournal::open(path.clone()).expect("journal should reopen");
+        assert_eq!(journal.recovered(), vec![buffer("a", "draft 2", 2)]);
+
+        // Once edited again, a buffer belongs to the current session.
+        journal.update(buffer("a", "draft 3", 4)).expect("update should be journaled");
+        assert!(journal.recovered().is_empty());
+
+        let reopened = BufferJournal::open(path).expect("journal should reopen");
+        assert_eq!(reopened.recovered(), vec![buffer("a", "draft 3", 4)]);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
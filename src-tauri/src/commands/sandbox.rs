@@ -0,0 +1,329 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use super::error::CommandError;
+use super::file::atomic_write_file;
+use super::paths::IpcPath;
+use crate::WorkspaceAccess;
+
+/// How many opened folders and files are remembered across launches.
+const MAX_REMEMBERED_GRANTS: usize = 50;
+
+fn access_denied(path: &Path) -> CommandError {
+    CommandError::OutOfWorkspace(IpcPath::from(path))
+}
+
+/// Resolve `path` to where it really points, for paths that may not exist yet.
+///
+/// The longest existing ancestor is canonicalised and the missing components are
+/// appended; a `..` among the missing components is refused, since it cannot be
+/// resolved safely. Unless `follow_final` is set, a symbolic link in the last
+/// component is left unresolved so the link itself can be renamed or deleted.
+fn resolve(path: &Path, follow_final: bool) -> Result<PathBuf, CommandError> {
+    if !path.is_absolute() {
+        return Err(access_denied(path));
+    }
+
+    let (base, final_name) = match (follow_final, path.parent(), path.file_name()) {
+        (false, Some(parent), Some(name)) => (parent, Some(name)),
+        _ => (path, None),
+    };
+
+    let mut existing = base.to_path_buf();
+    let mut missing = Vec::new();
+    let mut resolved = loop {
+        match fs::canonicalize(&existing) {
+            Ok(canonical) => break canonical,
+            Err(_) => {
+                let name = existing
+                    .file_name()
+                    .ok_or_else(|| access_denied(path))?
+                    .to_os_string();
+                missing.push(name);
+                if !existing.pop() {
+                    return Err(access_denied(path));
+                }
+            }
+        }
+    };
+
+    for name in missing.iter().rev().map(|name| name.as_os_str()).chain(final_name) {
+        resolved.push(name);
+    }
+
+    Ok(resolved)
+}
+
+fn check_resolved(
+    access: &WorkspaceAccess,
+    path: &Path,
+    resolved: &Path,
+) -> Result<(), CommandError> {
+    let granted = access
+        .granted
+        .lock()
+        .map_err(|_| "Failed to lock workspace access")?;
+
+    if granted.iter().any(|root| resolved.starts_with(root)) {
+        Ok(())
+    } else {
+        Err(access_denied(path))
+    }
+}
+
+/// Refuse `path` unless it lies inside a folder or is a file the user opened,
+/// following `..` and symbolic links.
+pub(crate) fn check_access(access: &WorkspaceAccess, path: &Path) -> Result<(), CommandError> {
+    check_resolved(access, path, &resolve(path, true)?)
+}
+
+/// Like `check_access`, but for operations on the entry itself (rename, move,
+/// delete), where a symbolic link is acted on rather than its target.
+pub(crate) fn check_entry_access(access: &WorkspaceAccess, path: &Path) -> Result<(), CommandError> {
+    check_resolved(access, path, &resolve(path, false)?)
+}
+
+/// The innermost opened folder or file containing `path`, and `path` relative
+/// to it. A document opened on its own is its own workspace, and is named by
+/// its file name.
+pub(crate) fn workspace_of(
+    access: &WorkspaceAccess,
+    path: &Path,
+) -> Result<(PathBuf, PathBuf), CommandError> {
+    let resolved = resolve(path, true)?;
+    let granted = access
+        .granted
+        .lock()
+        .map_err(|_| "Failed to lock workspace access")?;
+
+    let root = granted
+        .iter()
+        .filter(|root| resolved.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .cloned()
+        .ok_or_else(|| access_denied(path))?;
+
+    let relative = match resolved.strip_prefix(&root) {
+        Ok(relative) if relative.as_os_str().is_empty() => resolved.file_name().map(PathBuf::from),
+        Ok(relative) => Some(relative.to_path_buf()),
+        Err(_) => None,
+    }
+    .ok_or_else(|| access_denied(path))?;
+
+    Ok((root, relative))
+}
+
+/// Record `path` as opened by the user, returning its resolved form.
+fn add_grant(access: &WorkspaceAccess, path: &Path) -> Result<PathBuf, CommandError> {
+    let resolved = resolve(path, true)?;
+    let mut granted = access
+        .granted
+        .lock()
+        .map_err(|_| "Failed to lock workspace access")?;
+
+    // Most recent last, so the oldest grants are forgotten first.
+    granted.retain(|root| *root != resolved);
+    granted.push(resolved.clone());
+
+    Ok(resolved)
+}
+
+/// Where the list of opened folders and files is persisted.
+fn grants_file(app: &AppHandle) -> Result<PathBuf, CommandError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+
+    Ok(data_dir.join("workspace-access.json"))
+}
+
+/// Let the asset protocol serve files from a granted path. A document opened on
+/// its own is allowed by itself; images it links to are read through
+/// `check_linked_image` instead of widening the scope to its folder.
+fn allow_assets(app: &AppHandle, resolved: &Path) {
+    let scope = app.asset_protocol_scope();
+    let _ = if resolved.is_dir() {
+        scope.allow_directory(resolved, true)
+    } else {
+        scope.allow_file(resolved)
+    };
+}
+
+fn is_image_file(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => matches!(
+            ext.to_lowercase().as_str(),
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg" | "bmp" | "avif" | "ico"
+        ),
+        None => false,
+    }
+}
+
+/// Refuse `target` unless it is accessible itself, or is an image below the
+/// folder of `document`, an accessible document that links to it. This lets a
+/// document opened on its own show its images without opening up its folder.
+pub(crate) fn check_linked_image(
+    access: &WorkspaceAccess,
+    document: &Path,
+    target: &Path,
+) -> Result<(), CommandError> {
+    check_access(access, document)?;
+    if check_access(access, target).is_ok() {
+        return Ok(());
+    }
+
+    let resolved = resolve(target, true)?;
+    let folder = resolve(document, true)?
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| access_denied(target))?;
+
+    if is_image_file(&resolved) && resolved.starts_with(folder) {
+        Ok(())
+    } else {
+        Err(access_denied(target))
+    }
+}
+
+/// Grant access to a folder or file the user picked in a native dialog.
+pub(crate) fn grant_access(app: &AppHandle, path: &Path) -> Result<(), CommandError> {
+    let access = app.state::<WorkspaceAccess>();
+    let resolved = add_grant(&access, path)?;
+    allow_assets(app, &resolved);
+
+    let remembered: Vec<IpcPath> = {
+        let granted = access
+            .granted
+            .lock()
+            .map_err(|_| "Failed to lock workspace access")?;
+        granted
+            .iter()
+            .rev()
+            .take(MAX_REMEMBERED_GRANTS)
+            .rev()
+            .map(IpcPath::from)
+            .collect()
+    };
+
+    let json = serde_json::to_string(&remembered)
+        .map_err(|e| format!("Failed to serialize workspace access: {}", e))?;
+    atomic_write_file(&grants_file(app)?, json)
+}
+
+/// Restore the folders and files opened in earlier sessions, so a workspace
+/// reopened on launch is accessible again. Paths that no longer exist are dropped.
+pub(crate) fn restore_access(app: &AppHandle) {
+    let Ok(file) = grants_file(app) else {
+        return;
+    };
+    let Some(remembered) = fs::read_to_string(file)
+        .ok()
+        .and_then(|json| serde_json::from_str::<Vec<IpcPath>>(&json).ok())
+    else {
+        return;
+    };
+
+    let access = app.state::<WorkspaceAccess>();
+    for path in remembered {
+        let path = path.to_path_buf();
+        if path.exists() {
+            if let Ok(resolved) = add_grant(&access, &path) {
+                allow_assets(app, &resolved);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_grant, check_access, check_entry_access, check_linked_image};
+    use crate::commands::error::CommandError;
+    use crate::WorkspaceAccess;
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+
+    #[test]
+    fn check_access_allows_granted_paths_and_refuses_escapes() {
+        let root = make_temp_dir("sandbox-escapes");
+        let workspace = root.join("workspace");
+        fs::create_dir_all(workspace.join("notes")).expect("failed to create workspace");
+        fs::write(root.join("secret.md"), "secret").expect("failed to write outside file");
+        fs::write(root.join("single.md"), "single").expect("failed to write single file");
+
+        let access = WorkspaceAccess::default();
+        assert!(check_access(&access, &workspace.join("notes")).is_err());
+
+        add_grant(&access, &workspace).expect("workspace should be granted");
+        add_grant(&access, &root.join("single.md")).expect("file should be granted");
+
+        assert!(check_access(&access, &workspace.join("notes")).is_ok());
+        assert!(check_access(&access, &workspace.join("new").join("draft.md")).is_ok());
+        assert!(check_access(&access, &root.join("single.md")).is_ok());
+
+        let denied = check_access(&access, &workspace.join("..").join("secret.md"))
+            .expect_err("parent escape should be refused");
+        assert!(matches!(denied, CommandError::OutOfWorkspace(_)));
+        assert!(check_access(&access, &workspace.join("missing").join("..").join("..").join("secret.md")).is_err());
+        assert!(check_access(&access, &root.join("secret.md")).is_err());
+        assert!(check_access(&access, std::path::Path::new("relative.md")).is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_workspace_can_be_removed_but_not_followed() {
+        use std::os::unix::fs::symlink;
+
+        let root = make_temp_dir("sandbox-symlinks");
+        let workspace = root.join("workspace");
+        fs::create_dir_all(&workspace).expect("failed to create workspace");
+        fs::write(root.join("secret.md"), "secret").expect("failed to write outside file");
+        symlink(root.join("secret.md"), workspace.join("link.md")).expect("failed to create link");
+
+        let access = WorkspaceAccess::default();
+        add_grant(&access, &workspace).expect("workspace should be granted");
+
+        assert!(check_access(&access, &workspace.join("link.md")).is_err());
+        assert!(check_entry_access(&access, &workspace.join("link.md")).is_ok());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn documents_opened_alone_reach_only_their_linked_images() {
+        let root = make_temp_dir("sandbox-linked-images");
+        fs::create_dir_all(root.join("images")).expect("failed to create image folder");
+        fs::write(root.join("single.md"), "![](images/photo.png)").expect("failed to write document");
+        fs::write(root.join("other.md"), "other").expect("failed to write sibling");
+        fs::write(root.join("images").join("photo.png"), "png").expect("failed to write image");
+
+        let access = WorkspaceAccess::default();
+        let document = root.join("single.md");
+        add_grant(&access, &document).expect("file should be granted");
+
+        assert!(check_linked_image(&access, &document, &root.join("images").join("photo.png")).is_ok());
+        assert!(check_linked_image(&access, &document, &root.join("other.md")).is_err());
+        assert!(check_access(&access, &root.join("images").join("photo.png")).is_err());
+        assert!(check_linked_image(&access, &root.join("other.md"), &root.join("images").join("photo.png")).is_err());
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn webview_reaches_files_only_through_checked_commands() {
+        let capability: serde_json::Value =
+            serde_json::from_str(include_str!("../../capabilities/default.json"))
+                .expect("capability should be valid JSON");
+        let permissions = capability["permissions"]
+            .as_array()
+            .expect("capability should list permissions");
+
+        // The fs plugin would bypass `check_access`, so the webview must not be granted it.
+        assert!(permissions
+            .iter()
+            .filter_map(|permission| permission.as_str())
+            .all(|permission| !permission.starts_with("fs:")));
+    }
+}
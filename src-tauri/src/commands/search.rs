@@ -0,0 +1,763 @@
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tauri::{AppHandle, Emitter, Manager, State};
+use super::encoding::decode_text;
+use super::error::CommandError;
+use super::file::{content_hash, is_markdown_file, save_document, workspace_files, SaveFormat, SaveOutcome};
+use super::paths::IpcPath;
+use super::sandbox::check_access;
+use crate::{SearchRegistry, WorkspaceAccess};
+
+/// Longest line excerpt sent back as match context.
+const MAX_CONTEXT_CHARS: usize = 240;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Search every text file rather than only markdown documents.
+    pub include_all_files: bool,
+    /// Stop after this many matches.
+    pub max_results: Option<usize>,
+}
+
+/// A single match. `line` and `column` are 1-based, `column` and `length` count characters.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SearchMatch {
+    pub path: IpcPath,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+    pub context: String,
+}
+
+/// Matches found in one file, streamed as `workspace-search-results`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResultsEvent {
+    pub search_id: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Emitted once as `workspace-search-complete` when a search ends.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchCompleteEvent {
+    pub search_id: String,
+    pub cancelled: bool,
+    pub files_searched: usize,
+    pub match_count: usize,
+}
+
+/// One pending replacement in a file's preview.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplacementPreview {
+    /// Position of this replacement within the file, used to accept a subset.
+    pub index: usize,
+    pub line: usize,
+    pub column: usize,
+    pub matched: String,
+    pub replacement: String,
+    pub context: String,
+}
+
+/// Replacements that would be made in one file, with the hash of the content they were computed from.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileReplacePreview {
+    pub path: IpcPath,
+    pub hash: String,
+    pub replacements: Vec<ReplacementPreview>,
+}
+
+/// A previewed file the user accepted. `indices` limits the replacements
+/// applied; all of them are applied when it is omitted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AcceptedReplace {
+    pub path: IpcPath,
+    pub hash: String,
+    pub indices: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplacedFile {
+    pub path: IpcPath,
+    pub replacements: usize,
+}
+
+/// An accepted file that could not be replaced in, with the reason.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FailedReplace {
+    pub path: IpcPath,
+    pub error: CommandError,
+}
+
+/// Result of applying a replace. Files in `changed_since_preview` were left
+/// untouched because their content no longer matches the preview.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ReplaceResult {
+    pub updated: Vec<ReplacedFile>,
+    pub changed_since_preview: Vec<IpcPath>,
+    pub failed: Vec<FailedReplace>,
+}
+
+/// A replacement located in file content.
+struct PlannedReplacement {
+    line: usize,
+    range: Range<usize>,
+    line_range: Range<usize>,
+    replacement: String,
+}
+
+/// A compiled search query. Whole-word mode is checked on each match rather
+/// than with `\b`, so queries that start or end with punctuation, such as
+/// `C++` or `#tag`, still match.
+pub(crate) struct SearchPattern {
+    regex: Regex,
+    whole_word: bool,
+}
+
+impl SearchPattern {
+    /// Every match in `line`, with its capture groups.
+    fn captures_in<'h>(&self, line: &'h str) -> Vec<Captures<'h>> {
+        let mut found = Vec::new();
+        let mut start = 0;
+
+        while start <= line.len() {
+            let Some(captures) = self.regex.captures_at(line, start) else {
+                break;
+            };
+            let whole = captures.get(0).expect("match should have a whole group");
+
+            if self.whole_word && !is_whole_word(line, whole.range()) {
+                // Retry from the next character: a shorter or later match may still stand alone.
+                start = next_char(line, whole.start());
+                continue;
+            }
+
+            start = if whole.is_empty() {
+                next_char(line, whole.end())
+            } else {
+                whole.end()
+            };
+            found.push(captures);
+        }
+
+        found
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether the match at `range` does not run into a word on either side. Only
+/// ends of the match that are word characters themselves need a boundary.
+fn is_whole_word(line: &str, range: Range<usize>) -> bool {
+    let matched = &line[range.clone()];
+    let joins_before = matched.chars().next().is_some_and(is_word_char)
+        && line[..range.start].chars().next_back().is_some_and(is_word_char);
+    let joins_after = matched.chars().next_back().is_some_and(is_word_char)
+        && line[range.end..].chars().next().is_some_and(is_word_char);
+
+    !joins_before && !joins_after
+}
+
+/// Byte offset of the character after the one at `index`.
+fn next_char(line: &str, index: usize) -> usize {
+    index + line[index..].chars().next().map_or(1, char::len_utf8)
+}
+
+/// Compile the query into a pattern honouring the search options.
+pub(crate) fn build_search_pattern(query: &str, options: &SearchOptions) -> Result<SearchPattern, CommandError> {
+    if query.is_empty() {
+        return Err(CommandError::Other("Search query is required".to_string()));
+    }
+
+    let pattern = if options.is_regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|e| CommandError::Other(format!("Invalid search pattern: {}", e)))?;
+
+    Ok(SearchPattern {
+        regex,
+        whole_word: options.whole_word,
+    })
+}
+
+/// Whether a file should be searched under the given options.
+pub(crate) fn is_searchable(path: &Path, options: &SearchOptions) -> bool {
+    options.include_all_files || is_markdown_file(path)
+}
+
+/// Find every match of `pattern` in `content`, line by line.
+fn search_content(path: &Path, content: &str, pattern: &SearchPattern) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        for captures in pattern.captures_in(line) {
+            let found = captures.get(0).expect("match should have a whole group");
+            if found.as_str().is_empty() {
+                continue;
+            }
+
+            let context: String = line.chars().take(MAX_CONTEXT_CHARS).collect();
+
+            matches.push(SearchMatch {
+                path: IpcPath::from(path),
+                line: index + 1,
+                column: line[..found.start()].chars().count() + 1,
+                length: found.as_str().chars().count(),
+                context,
+            });
+        }
+    }
+
+    matches
+}
+
+/// Locate every replacement in `content`, line by line like search. Regex
+/// replacements may refer to capture groups as `$1` or `${name}`.
+fn plan_replacements(
+    content: &str,
+    pattern: &SearchPattern,
+    replacement: &str,
+    options: &SearchOptions,
+) -> Vec<PlannedReplacement> {
+    let mut planned = Vec::new();
+    let mut offset = 0;
+
+    for (index, raw_line) in content.split_inclusive('\n').enumerate() {
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+
+        for captures in pattern.captures_in(line) {
+            let found = captures.get(0).expect("match should have a whole group");
+            if found.as_str().is_empty() {
+                continue;
+            }
+
+            let mut expanded = String::new();
+            if options.is_regex {
+                captures.expand(replacement, &mut expanded);
+            } else {
+                expanded.push_str(replacement);
+            }
+
+            planned.push(PlannedReplacement {
+                line: index + 1,
+                range: offset + found.start()..offset + found.end(),
+                line_range: offset..offset + line.len(),
+                replacement: expanded,
+            });
+        }
+
+        offset += raw_line.len();
+    }
+
+    planned
+}
+
+/// Apply the planned replacements whose position is in `accepted`, or all of them.
+fn apply_replacements(
+    content: &str,
+    planned: &[PlannedReplacement],
+    accepted: Option<&[usize]>,
+) -> (String, usize) {
+    let mut result = String::with_capacity(content.len());
+    let mut last = 0;
+    let mut applied = 0;
+
+    for (index, replacement) in planned.iter().enumerate() {
+        if accepted.is_some_and(|accepted| !accepted.contains(&index)) {
+            continue;
+        }
+
+        result.push_str(&content[last..replacement.range.start]);
+        result.push_str(&replacement.replacement);
+        last = replacement.range.end;
+        applied += 1;
+    }
+
+    result.push_str(&content[last..]);
+    (result, applied)
+}
+
+fn preview_replacements(
+    path: &Path,
+    content: &str,
+    hash: String,
+    planned: &[PlannedReplacement],
+) -> FileReplacePreview {
+    let replacements = planned
+        .iter()
+        .enumerate()
+        .map(|(index, replacement)| {
+            let line = &content[replacement.line_range.clone()];
+            ReplacementPreview {
+                index,
+                line: replacement.line,
+                column: content[replacement.line_range.start..replacement.range.start]
+                    .chars()
+                    .count()
+                    + 1,
+                matched: content[replacement.range.clone()].to_string(),
+                replacement: replacement.replacement.clone(),
+                context: line.chars().take(MAX_CONTEXT_CHARS).collect(),
+            }
+        })
+        .collect();
+
+    FileReplacePreview {
+        path: IpcPath::from(path),
+        hash,
+        replacements,
+    }
+}
+
+/// Preview of every replacement across the workspace, one entry per affected file.
+fn preview_workspace(
+    root: &Path,
+    pattern: &SearchPattern,
+    replacement: &str,
+    options: &SearchOptions,
+) -> Vec<FileReplacePreview> {
+    let mut previews = Vec::new();
+
+    for file in workspace_files(root) {
+        if !is_searchable(&file, options) {
+            continue;
+        }
+
+        let Ok(bytes) = fs::read(&file) else {
+            continue;
+        };
+        let (content, _) = decode_text(&bytes);
+
+        let planned = plan_replacements(&content, pattern, replacement, options);
+        if !planned.is_empty() {
+            previews.push(preview_replacements(&file, &content, content_hash(&bytes), &planned));
+        }
+    }
+
+    previews
+}
+
+/// Re-plan one accepted file from its current content. Returns `Ok(None)` when
+/// the file changed since the preview.
+fn replace_accepted(
+    accepted: &AcceptedReplace,
+    pattern: &SearchPattern,
+    replacement: &str,
+    options: &SearchOptions,
+) -> Result<Option<(String, usize)>, CommandError> {
+    let path = accepted.path.to_path_buf();
+    let bytes = fs::read(&path).map_err(|e| CommandError::io(&path, "Failed to read file", e))?;
+
+    if content_hash(&bytes) != accepted.hash {
+        return Ok(None);
+    }
+
+    let (content, _) = decode_text(&bytes);
+    let planned = plan_replacements(&content, pattern, replacement, options);
+    Ok(Some(apply_replacements(&content, &planned, accepted.indices.as_deref())))
+}
+
+/// Write one accepted file through the editor's save path, so it keeps its
+/// encoding and line endings and gets a backup and history entry. Returns
+/// `Ok(None)` when the file changed since the preview.
+fn apply_accepted(
+    app: &AppHandle,
+    accepted: &AcceptedReplace,
+    pattern: &SearchPattern,
+    replacement: &str,
+    options: &SearchOptions,
+) -> Result<Option<usize>, CommandError> {
+    let Some((updated, applied)) = replace_accepted(accepted, pattern, replacement, options)? else {
+        return Ok(None);
+    };
+
+    if applied == 0 {
+        return Ok(Some(0));
+    }
+
+    let path = accepted.path.to_path_buf();
+    match save_document(app, &path, &updated, None, Some(&accepted.hash), SaveFormat::default(), false)? {
+        (SaveOutcome::Saved { .. }, _) => Ok(Some(applied)),
+        (SaveOutcome::Conflict { .. }, _) => Ok(None),
+    }
+}
+
+/// Walk the workspace, reporting matches file by file until done or cancelled.
+fn run_search(
+    root: &Path,
+    pattern: &SearchPattern,
+    options: &SearchOptions,
+    cancelled: &AtomicBool,
+    mut on_matches: impl FnMut(Vec<SearchMatch>),
+) -> SearchCompleteEvent {
+    let mut files_searched = 0;
+    let mut match_count = 0;
+    let limit = options.max_results.unwrap_or(usize::MAX);
+
+    for file in workspace_files(root) {
+        if cancelled.load(Ordering::Relaxed) || match_count >= limit {
+            break;
+        }
+
+        if !is_searchable(&file, options) {
+            continue;
+        }
+
+        let Ok(bytes) = fs::read(&file) else {
+            continue;
+        };
+        let (content, _) = decode_text(&bytes);
+
+        files_searched += 1;
+
+        let mut matches = search_content(&file, &content, pattern);
+        matches.truncate(limit - match_count);
+
+        if !matches.is_empty() {
+            match_count += matches.len();
+            on_matches(matches);
+        }
+    }
+
+    SearchCompleteEvent {
+        search_id: String::new(),
+        cancelled: cancelled.load(Ordering::Relaxed),
+        files_searched,
+        match_count,
+    }
+}
+
+/// Search the workspace in the background.
+///
+/// Results are streamed as `workspace-search-results` events tagged with
+/// `search_id`, followed by a single `workspace-search-complete` event.
+#[tauri::command]
+pub async fn search_workspace(
+    app: AppHandle,
+    state: State<'_, SearchRegistry>,
+    search_id: String,
+    root: IpcPath,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<(), CommandError> {
+    let options = options.unwrap_or_default();
+    let pattern = build_search_pattern(&query, &options)?;
+
+    let root = root.to_path_buf();
+    check_access(&app.state::<WorkspaceAccess>(), &root)?;
+    if !root.is_dir() {
+        return Err(CommandError::Other(format!("{} is not a directory", root.display())));
+    }
+
+    let cancelled = {
+        let mut searches = state
+            .searches
+            .lock()
+            .map_err(|_| "Failed to lock search registry")?;
+
+        if searches.contains_key(&search_id) {
+            return Err(CommandError::Other("A search with that id is already running".to_string()));
+        }
+
+        let flag = Arc::new(AtomicBool::new(false));
+        searches.insert(search_id.clone(), flag.clone());
+        flag
+    };
+
+    let app_handle = app.clone();
+
+    thread::spawn(move || {
+        let complete = run_search(&root, &pattern, &options, &cancelled, |matches| {
+            let _ = app_handle.emit(
+                "workspace-search-results",
+                SearchResultsEvent {
+                    search_id: search_id.clone(),
+                    matches,
+                },
+            );
+        });
+
+        let registry = app_handle.state::<SearchRegistry>();
+        if let Ok(mut searches) = registry.searches.lock() {
+            if searches.get(&search_id).is_some_and(|flag| Arc::ptr_eq(flag, &cancelled)) {
+                searches.remove(&search_id);
+            }
+        }
+
+        let _ = app_handle.emit(
+            "workspace-search-complete",
+            SearchCompleteEvent {
+                search_id,
+                ..complete
+            },
+        );
+    });
+
+    Ok(())
+}
+
+/// Compute every replacement of `query` with `replacement` across the workspace
+/// without changing any file.
+#[tauri::command]
+pub async fn preview_workspace_replace(
+    access: State<'_, WorkspaceAccess>,
+    root: IpcPath,
+    query: String,
+    replacement: String,
+    options: Option<SearchOptions>,
+) -> Result<Vec<FileReplacePreview>, CommandError> {
+    let options = options.unwrap_or_default();
+    let pattern = build_search_pattern(&query, &options)?;
+
+    let root = root.to_path_buf();
+    check_access(&access, &root)?;
+    if !root.is_dir() {
+        return Err(CommandError::Other(format!("{} is not a directory", root.display())));
+    }
+
+    Ok(preview_workspace(&root, &pattern, &replacement, &options))
+}
+
+/// Apply the accepted part of a replace preview.
+///
+/// Each file is only rewritten if its content still hashes to the value from
+/// the preview; otherwise it is reported in `changed_since_preview`. Files are
+/// saved like editor saves. Files outside the opened workspaces, or that cannot
+/// be saved, are reported in `failed` with the reason.
+#[tauri::command]
+pub async fn apply_workspace_replace(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    query: String,
+    replacement: String,
+    options: Option<SearchOptions>,
+    accepted: Vec<AcceptedReplace>,
+) -> Result<ReplaceResult, CommandError> {
+    let options = options.unwrap_or_default();
+    let pattern = build_search_pattern(&query, &options)?;
+    let mut result = ReplaceResult::default();
+
+    for file in &accepted {
+        if let Err(error) = check_access(&access, &file.path.to_path_buf()) {
+            result.failed.push(FailedReplace {
+                path: file.path.clone(),
+                error,
+            });
+            continue;
+        }
+
+        match apply_accepted(&app, file, &pattern, &replacement, &options) {
+            Ok(Some(0)) => {}
+            Ok(Some(replacements)) => {
+                result.updated.push(ReplacedFile {
+                    path: file.path.clone(),
+                    replacements,
+                });
+            }
+            Ok(None) => result.changed_since_preview.push(file.path.clone()),
+            Err(error) => result.failed.push(FailedReplace {
+                path: file.path.clone(),
+                error,
+            }),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Cancel a running workspace search.
+#[tauri::command]
+pub async fn cancel_workspace_search(
+    state: State<'_, SearchRegistry>,
+    search_id: String,
+) -> Result<(), CommandError> {
+    let flag = state
+        .searches
+        .lock()
+        .map_err(|_| "Failed to lock search registry")?
+        .remove(&search_id);
+
+    if let Some(flag) = flag {
+        flag.store(true, Ordering::Relaxed);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_search_pattern, preview_workspace, replace_accepted, run_search, search_content,
+        AcceptedReplace, SearchOptions, SearchPattern,
+    };
+    use crate::commands::test_support::make_temp_dir;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::atomic::AtomicBool;
+
+    fn matched<'h>(pattern: &SearchPattern, line: &'h str) -> Vec<&'h str> {
+        pattern
+            .captures_in(line)
+            .iter()
+            .filter_map(|captures| captures.get(0).map(|found| found.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn build_search_pattern_honours_case_and_whole_word_options() {
+        let plain = build_search_pattern("note.md", &SearchOptions::default())
+            .expect("plain query should compile");
+        assert_eq!(matched(&plain, "See NOTE.md"), vec!["NOTE.md"]);
+        assert!(matched(&plain, "notexmd").is_empty());
+
+        let whole_word = build_search_pattern(
+            "cat",
+            &SearchOptions {
+                case_sensitive: true,
+                whole_word: true,
+                ..SearchOptions::default()
+            },
+        )
+        .expect("whole word query should compile");
+        assert_eq!(matched(&whole_word, "a cat sat"), vec!["cat"]);
+        assert!(matched(&whole_word, "concatenate").is_empty());
+        assert!(matched(&whole_word, "Cat").is_empty());
+
+        let invalid = build_search_pattern(
+            "(unclosed",
+            &SearchOptions {
+                is_regex: true,
+                ..SearchOptions::default()
+            },
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn whole_word_matches_queries_with_punctuation_at_either_end() {
+        let options = SearchOptions {
+            whole_word: true,
+            ..SearchOptions::default()
+        };
+
+        let cpp = build_search_pattern("C++", &options).expect("query should compile");
+        assert_eq!(matched(&cpp, "C++ and ObjC++, C++."), vec!["C++", "C++"]);
+
+        let tag = build_search_pattern("#tag", &options).expect("query should compile");
+        assert_eq!(matched(&tag, "#tag, x#tag and #tags"), vec!["#tag", "#tag"]);
+
+        let runs = build_search_pattern(
+            r"a+",
+            &SearchOptions {
+                is_regex: true,
+                ..options
+            },
+        )
+        .expect("query should compile");
+        assert_eq!(matched(&runs, "baa aa"), vec!["aa"]);
+    }
+
+    #[test]
+    fn search_content_reports_character_columns_per_line() {
+        let pattern = build_search_pattern("kea", &SearchOptions::default()).expect("query should compile");
+        let matches = search_content(Path::new("/ws/note.md"), "# Título\nthe kéa and the Kea\n", &pattern);
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].column, 17);
+        assert_eq!(matches[0].length, 3);
+        assert_eq!(matches[0].context, "the kéa and the Kea");
+    }
+
+    #[test]
+    fn run_search_skips_non_markdown_and_respects_max_results() {
+        let root = make_temp_dir("search-run");
+        fs::write(root.join("a.md"), "term\nterm\n").expect("failed to write markdown file");
+        fs::write(root.join("b.txt"), "term\n").expect("failed to write text file");
+        // "café" in Windows-1252, which is not valid UTF-8.
+        fs::write(root.join("c.md"), b"caf\xe9 term\n").expect("failed to write legacy note");
+
+        let pattern = build_search_pattern("term", &SearchOptions::default()).expect("query should compile");
+        let mut streamed = Vec::new();
+        let complete = run_search(
+            &root,
+            &pattern,
+            &SearchOptions::default(),
+            &AtomicBool::new(false),
+            |matches| streamed.extend(matches),
+        );
+        assert_eq!(complete.files_searched, 2);
+        assert_eq!(complete.match_count, 3);
+        assert_eq!(streamed.len(), 3);
+
+        let limited = SearchOptions {
+            include_all_files: true,
+            max_results: Some(1),
+            ..SearchOptions::default()
+        };
+        let complete = run_search(&root, &pattern, &limited, &AtomicBool::new(false), |_| {});
+        assert_eq!(complete.match_count, 1);
+
+        let complete = run_search(&root, &pattern, &limited, &AtomicBool::new(true), |_| {});
+        assert!(complete.cancelled);
+        assert_eq!(complete.files_searched, 0);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn replace_preview_expands_capture_groups_and_applies_accepted_subset() {
+        let root = make_temp_dir("search-replace");
+        let note = root.join("note.md");
+        fs::write(&note, "due 2024-01-05\r\nand 2024-02-10\n").expect("failed to write note");
+
+        let options = SearchOptions {
+            is_regex: true,
+            ..SearchOptions::default()
+        };
+        let pattern = build_search_pattern(r"(\d{4})-(\d{2})-(\d{2})", &options).expect("query should compile");
+        let previews = preview_workspace(&root, &pattern, "$3/$2/$1", &options);
+
+        assert_eq!(previews.len(), 1);
+        let preview = &previews[0];
+        assert_eq!(preview.replacements.len(), 2);
+        assert_eq!(preview.replacements[1].line, 2);
+        assert_eq!(preview.replacements[1].column, 5);
+        assert_eq!(preview.replacements[1].matched, "2024-02-10");
+        assert_eq!(preview.replacements[1].replacement, "10/02/2024");
+        assert_eq!(fs::read_to_string(&note).expect("note should be readable"), "due 2024-01-05\r\nand 2024-02-10\n");
+
+        let accepted = AcceptedReplace {
+            path: preview.path.clone(),
+            hash: preview.hash.clone(),
+            indices: Some(vec![1]),
+        };
+        let replaced = replace_accepted(&accepted, &pattern, "$3/$2/$1", &options)
+            .expect("replace should succeed")
+            .expect("file should be unchanged since preview");
+        assert_eq!(replaced, ("due 2024-01-05\r\nand 10/02/2024\n".to_string(), 1));
+
+        fs::write(&note, "due 2024-01-05\n").expect("failed to change note");
+        let stale = replace_accepted(&accepted, &pattern, "$3/$2/$1", &options).expect("replace should succeed");
+        assert!(stale.is_none());
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
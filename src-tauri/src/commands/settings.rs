@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use super::error::CommandError;
+use super::file::{atomic_write_file, SaveFormat};
+use super::paths::IpcPath;
+use super::sandbox::{check_access, workspace_of};
+use crate::{WorkspaceAccess, WorkspaceSettings};
+
+/// What the user chose for one workspace. Workspaces left at the defaults are
+/// not saved.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct WorkspaceConfig {
+    /// How many earlier versions of each file are kept as backups; 0 when off.
+    pub backups: usize,
+    /// Whether the sidebar lists ignored items, flagged, instead of leaving them out.
+    pub show_ignored: bool,
+    /// How files are encoded on save where the save itself does not say.
+    pub save_format: SaveFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedWorkspace {
+    root: IpcPath,
+    #[serde(flatten)]
+    config: WorkspaceConfig,
+}
+
+fn settings_file(app: &AppHandle) -> Result<PathBuf, CommandError> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+
+    Ok(data_dir.join("workspaces.json"))
+}
+
+fn load_settings(file: &Path) -> HashMap<PathBuf, WorkspaceConfig> {
+    let saved: Vec<SavedWorkspace> = fs::read_to_string(file)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+
+    saved
+        .into_iter()
+        .map(|workspace| (workspace.root.to_path_buf(), workspace.config))
+        .collect()
+}
+
+fn save_settings(file: &Path, workspaces: &HashMap<PathBuf, WorkspaceConfig>) -> Result<(), CommandError> {
+    let saved: Vec<SavedWorkspace> = workspaces
+        .iter()
+        .filter(|(_, config)| **config != WorkspaceConfig::default())
+        .map(|(root, config)| SavedWorkspace {
+            root: IpcPath::from(root),
+            config: config.clone(),
+        })
+        .collect();
+
+    let json = serde_json::to_string(&saved)
+        .map_err(|e| format!("Failed to serialize workspace settings: {}", e))?;
+    atomic_write_file(file, json)
+}
+
+/// Run `action` on the per-workspace settings, loading them on first use.
+fn with_settings<T>(
+    app: &AppHandle,
+    action: impl FnOnce(&mut HashMap<PathBuf, WorkspaceConfig>) -> T,
+) -> Result<T, CommandError> {
+    let settings = app.state::<WorkspaceSettings>();
+    let mut workspaces = settings
+        .workspaces
+        .lock()
+        .map_err(|_| "Failed to lock workspace settings")?;
+
+    let workspaces = workspaces.get_or_insert_with(|| {
+        settings_file(app).map(|file| load_settings(&file)).unwrap_or_default()
+    });
+
+    Ok(action(workspaces))
+}
+
+/// The settings of the workspace rooted at `root`, as returned by `workspace_of`.
+pub(crate) fn workspace_config(app: &AppHandle, root: &Path) -> Result<WorkspaceConfig, CommandError> {
+    with_settings(app, |workspaces| workspaces.get(root).cloned().unwrap_or_default())
+}
+
+/// The settings of the workspace containing `path`, or the defaults when it is
+/// in none.
+pub(crate) fn settings_for(app: &AppHandle, path: &Path) -> WorkspaceConfig {
+    workspace_of(&app.state::<WorkspaceAccess>(), path)
+        .and_then(|(root, _)| workspace_config(app, &root))
+        .unwrap_or_default()
+}
+
+/// Change the settings of the workspace containing `root` and save them.
+pub(crate) fn update_workspace_config(
+    app: &AppHandle,
+    access: &WorkspaceAccess,
+    root: &Path,
+    update: impl FnOnce(&mut WorkspaceConfig),
+) -> Result<(), CommandError> {
+    check_access(access, root)?;
+    let (root, _) = workspace_of(access, root)?;
+    let file = settings_file(app)?;
+
+    with_settings(app, |workspaces| {
+        update(workspaces.entry(root).or_default());
+        save_settings(&file, workspaces)
+    })?
+}
+
+/// Whether the sidebar of a workspace lists ignored items.
+#[tauri::command]
+pub async fn get_workspace_show_ignored(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    root: IpcPath,
+) -> Result<bool, CommandError> {
+    let root = root.to_path_buf();
+    check_access(&access, &root)?;
+    let (root, _) = workspace_of(&access, &root)?;
+
+    Ok(workspace_config(&app, &root)?.show_ignored)
+}
+
+/// List ignored items in a workspace's sidebar, flagged, or leave them out.
+/// Listings and watchers started without an explicit `show_ignored` follow it.
+#[tauri::command]
+pub async fn set_workspace_show_ignored(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    root: IpcPath,
+    show_ignored: bool,
+) -> Result<(), CommandError> {
+    update_workspace_config(&app, &access, &root.to_path_buf(), |config| {
+        config.show_ignored = show_ignored;
+    })
+}
+
+/// The encoding and line style a workspace enforces on save.
+#[tauri::command]
+pub async fn get_workspace_save_format(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    root: IpcPath,
+) -> Result<SaveFormat, CommandError> {
+    let root = root.to_path_buf();
+    check_access(&access, &root)?;
+    let (root, _) = workspace_of(&access, &root)?;
+
+    Ok(workspace_config(&app, &root)?.save_format)
+}
+
+/// Enforce an encoding or line style on every save in a workspace, for example
+/// LF line endings with a final newline. Fields left out keep what each file has.
+#[tauri::command]
+pub async fn set_workspace_save_format(
+    app: AppHandle,
+    access: State<'_, WorkspaceAccess>,
+    root: IpcPath,
+    format: SaveFormat,
+) -> Result<(), CommandError> {
+    update_workspace_config(&app, &access, &root.to_path_buf(), |config| {
+        config.save_format = format;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_settings, save_settings, WorkspaceConfig};
+    use crate::commands::file::SaveFormat;
+    use crate::commands::line_endings::LineEnding;
+    use crate::commands::test_support::make_temp_dir;
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[test]
+    fn settings_round_trip_and_skip_default_workspaces() {
+        let root = make_temp_dir("workspace-settings");
+        let file = root.join("workspaces.json");
+
+        let mut workspaces = HashMap::new();
+        workspaces.insert(
+            root.join("notes"),
+            WorkspaceConfig {
+                backups: 3,
+                show_ignored: true,
+                save_format: SaveFormat {
+                    line_ending: Some(LineEnding::Lf),
+                    ..SaveFormat::default()
+                },
+            },
+        );
+        workspaces.insert(root.join("plain"), WorkspaceConfig::default());
+        save_settings(&file, &workspaces).expect("settings should be saved");
+
+        let loaded = load_settings(&file);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[&root.join("notes")], workspaces[&root.join("notes")]);
+
+        fs::write(&file, r#"[{"root":"/old","backups":2}]"#).expect("failed to write settings");
+        let partial = load_settings(&file);
+        assert_eq!(partial.values().next().map(|config| config.show_ignored), Some(false));
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
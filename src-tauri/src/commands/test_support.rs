@@ -0,0 +1,15 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Create a fresh, uniquely named directory under the system temp directory.
+pub(crate) fn make_temp_dir(test_name: &str) -> PathBuf {
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be after unix epoch")
+        .as_nanos();
+
+    let dir = std::env::temp_dir().join(format!("kea-tests-{}-{}", test_name, unique));
+    fs::create_dir_all(&dir).expect("failed to create temporary test directory");
+    dir
+}
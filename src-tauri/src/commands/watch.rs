@@ -0,0 +1,1034 @@
+use notify_debouncer_full::file_id::{get_file_id, FileId};
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{
+    new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache,
+};
+use serde::{Deserialize, Serialize};
+use similar::{DiffTag, TextDiff};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use super::error::CommandError;
+use super::file::{
+    build_file_entry, content_hash, is_hidden, DirectoryListing, DirectorySort, FileEntry,
+};
+use super::ignore::IgnoreRules;
+use super::encoding::decode_text;
+use super::index::update_indexed_paths;
+use super::paths::IpcPath;
+use super::quick_open::invalidate_quick_open;
+use super::sandbox::check_access;
+use super::settings::settings_for;
+use crate::{FileWatchRegistry, QuickOpenRegistry, SearchIndexRegistry, WorkspaceAccess};
+
+/// How long bursts of notifications for the same path are coalesced before emitting.
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// Change to a single watched file.
+///
+/// `kind` is `modified`, `removed`, `renamed` or `replaced` (swapped for a new
+/// file, as atomic-save tools do). For renames `path` is the new location and
+/// `old_path` the watched one; the watch itself stays on `old_path` until the
+/// frontend moves it.
+///
+/// Files watched with `include_content` also carry the content read for the
+/// event and, when a previous version is known, a line diff against it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileWatchEvent {
+    pub path: IpcPath,
+    pub kind: String,
+    pub old_path: Option<IpcPath>,
+    pub hash: Option<String>,
+    pub content: Option<String>,
+    pub diff: Option<Vec<DiffHunk>>,
+}
+
+/// Replace `old_len` lines of the previous version, starting at `old_start`,
+/// with `new_lines` (which keep their line endings). Line numbers are 0-based.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_lines: Vec<String>,
+}
+
+/// Incremental change to a watched workspace tree.
+///
+/// `kind` is `added`, `removed` or `renamed`; `entry` is set for additions and
+/// renames and `old_path` only for renames.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceWatchEvent {
+    pub root: IpcPath,
+    pub kind: String,
+    pub path: IpcPath,
+    pub old_path: Option<IpcPath>,
+    pub entry: Option<FileEntry>,
+}
+
+/// Last known state of a watched file, keyed by its canonical location.
+struct WatchedFile {
+    /// Path as supplied by the frontend, echoed back in events.
+    path: IpcPath,
+    exists: bool,
+    /// Hash of the content last seen on disk or last written by Kea itself.
+    hash: Option<String>,
+    /// Filesystem identity, used to tell in-place writes from replacements.
+    id: Option<FileId>,
+    include_content: bool,
+    /// Last known content, kept only when `include_content` is set.
+    content: Option<String>,
+}
+
+impl WatchedFile {
+    fn read(path: &IpcPath, key: &Path, include_content: bool) -> Self {
+        let bytes = fs::read(key).ok();
+
+        Self {
+            path: path.clone(),
+            exists: key.exists(),
+            hash: bytes.as_deref().map(content_hash),
+            id: get_file_id(key).ok(),
+            include_content,
+            content: bytes.filter(|_| include_content).map(decode_content),
+        }
+    }
+}
+
+type WatchedFiles = Arc<Mutex<HashMap<PathBuf, WatchedFile>>>;
+
+/// One OS notification watcher multiplexing every watched file.
+///
+/// Files are watched through their parent directory so that atomic replacements,
+/// which swap the underlying inode, keep being reported.
+pub struct FileWatcher {
+    debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+    files: WatchedFiles,
+    directories: HashMap<PathBuf, usize>,
+}
+
+impl FileWatcher {
+    fn new(app: AppHandle) -> Result<Self, CommandError> {
+        let files: WatchedFiles = Arc::new(Mutex::new(HashMap::new()));
+        let handler_files = files.clone();
+
+        let debouncer = new_debouncer(
+            DEBOUNCE_TIMEOUT,
+            None,
+            move |result: DebounceEventResult| {
+                let Ok(events) = result else {
+                    return;
+                };
+                let Ok(mut files) = handler_files.lock() else {
+                    return;
+                };
+
+                for event in collect_watch_events(&mut files, &events) {
+                    let _ = app.emit("file-watch-event", event);
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to start file watcher: {}", e))?;
+
+        Ok(Self {
+            debouncer,
+            files,
+            directories: HashMap::new(),
+        })
+    }
+
+    fn watch(&mut self, path: &IpcPath, include_content: bool) -> Result<(), CommandError> {
+        let (directory, key) = watch_key(&path.to_path_buf())?;
+
+        let mut files = self
+            .files
+            .lock()
+            .map_err(|_| "Failed to lock watcher registry")?;
+
+        if let Some(file) = files.get_mut(&key) {
+            if include_content && !file.include_content {
+                *file = WatchedFile::read(&file.path, &key, true);
+            }
+            return Ok(());
+        }
+
+        let count = self.directories.entry(directory.clone()).or_insert(0);
+        if *count == 0 {
+            if let Err(e) = self.debouncer.watch(&directory, RecursiveMode::NonRecursive) {
+                self.directories.remove(&directory);
+                return Err(CommandError::Other(format!("Failed to watch file: {}", e)));
+            }
+        }
+        *count += 1;
+
+        let file = WatchedFile::read(path, &key, include_content);
+        files.insert(key, file);
+
+        Ok(())
+    }
+
+    fn record_write(&mut self, path: &Path, bytes: &[u8]) {
+        let Ok((_, key)) = watch_key(path) else {
+            return;
+        };
+        let Ok(mut files) = self.files.lock() else {
+            return;
+        };
+
+        if let Some(file) = files.get_mut(&key) {
+            file.exists = true;
+            file.hash = Some(content_hash(bytes));
+            file.id = get_file_id(&key).ok();
+            if file.include_content {
+                file.content = Some(decode_text(bytes).0);
+            }
+        }
+    }
+
+    fn unwatch(&mut self, path: &IpcPath) -> Result<(), CommandError> {
+        let removed = {
+            let mut files = self
+                .files
+                .lock()
+                .map_err(|_| "Failed to lock watcher registry")?;
+            let key = files
+                .iter()
+                .find(|(_, file)| file.path == *path)
+                .map(|(key, _)| key.clone());
+            key.filter(|key| files.remove(key).is_some())
+        };
+
+        if let Some(directory) = removed.as_deref().and_then(Path::parent) {
+            self.release_directory(directory);
+        }
+
+        Ok(())
+    }
+
+    fn unwatch_all(&mut self) -> Result<(), CommandError> {
+        self.files
+            .lock()
+            .map_err(|_| "Failed to lock watcher registry")?
+            .clear();
+
+        for (directory, _) in self.directories.drain() {
+            let _ = self.debouncer.unwatch(&directory);
+        }
+
+        Ok(())
+    }
+
+    fn release_directory(&mut self, directory: &Path) {
+        let Some(count) = self.directories.get_mut(directory) else {
+            return;
+        };
+
+        *count -= 1;
+        if *count == 0 {
+            self.directories.remove(directory);
+            let _ = self.debouncer.unwatch(directory);
+        }
+    }
+}
+
+/// Remember bytes Kea itself just wrote so the watcher does not echo them back
+/// as an external change.
+pub(crate) fn record_own_write(registry: &FileWatchRegistry, path: &Path, bytes: &[u8]) {
+    let Ok(mut watcher) = registry.watcher.lock() else {
+        return;
+    };
+
+    if let Some(watcher) = watcher.as_mut() {
+        watcher.record_write(path, bytes);
+    }
+}
+
+fn decode_content(bytes: Vec<u8>) -> String {
+    decode_text(&bytes).0
+}
+
+/// Line-level diff from `old` to `new`, as the hunks needed to turn one into the other.
+pub(crate) fn line_diff(old: &str, new: &str) -> Vec<DiffHunk> {
+    let diff = TextDiff::from_lines(old, new);
+    let new_lines = diff.new_slices();
+
+    diff.ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| {
+            let old_range = op.old_range();
+            let new_range = op.new_range();
+
+            DiffHunk {
+                old_start: old_range.start,
+                old_len: old_range.len(),
+                new_start: new_range.start,
+                new_lines: new_lines[new_range].iter().map(|line| line.to_string()).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Resolve the directory to watch and the key events will be reported under.
+fn watch_key(path: &Path) -> Result<(PathBuf, PathBuf), CommandError> {
+    let file_name = path.file_name().ok_or("Invalid file path")?;
+    let parent = path
+        .parent()
+        .ok_or("Invalid file path: missing parent directory")?;
+
+    let directory = fs::canonicalize(parent)
+        .map_err(|e| CommandError::io(parent, "Failed to resolve parent directory", e))?;
+    let key = directory.join(file_name);
+
+    Ok((directory, key))
+}
+
+/// The renames in a debounced batch.
+///
+/// Backends report a rename either as one event carrying both paths or as
+/// separate `From` and `To` events sharing a tracker, which is how a move
+/// between two watched directories arrives on Linux.
+struct Renames<'a> {
+    /// Source and destination, keyed by the index of the event completing the rename.
+    completed: HashMap<usize, (&'a PathBuf, &'a PathBuf)>,
+    /// Indices of the other events that belong to a completed rename.
+    consumed: HashSet<usize>,
+}
+
+fn collect_renames(events: &[DebouncedEvent]) -> Renames<'_> {
+    let mut renames = Renames {
+        completed: HashMap::new(),
+        consumed: HashSet::new(),
+    };
+    let mut pending: HashMap<usize, (usize, &PathBuf)> = HashMap::new();
+    let mut paired: HashSet<usize> = HashSet::new();
+
+    for (index, event) in events.iter().enumerate() {
+        let EventKind::Modify(ModifyKind::Name(mode)) = event.kind else {
+            continue;
+        };
+        let tracker = event.attrs.tracker();
+
+        match (mode, event.paths.as_slice(), tracker) {
+            (RenameMode::Both, [from, to], _) => {
+                // inotify follows the `From` and `To` halves with a combined event.
+                if tracker.is_some_and(|tracker| !paired.insert(tracker)) {
+                    renames.consumed.insert(index);
+                } else {
+                    renames.completed.insert(index, (from, to));
+                }
+            }
+            (RenameMode::From, [from], Some(tracker)) => {
+                pending.insert(tracker, (index, from));
+            }
+            (RenameMode::To, [to], Some(tracker)) => {
+                if let Some((from_index, from)) = pending.remove(&tracker) {
+                    renames.consumed.insert(from_index);
+                    if paired.insert(tracker) {
+                        renames.completed.insert(index, (from, to));
+                    } else {
+                        renames.consumed.insert(index);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    renames
+}
+
+/// Turn a debounced batch into at most one event per watched file, updating the
+/// last known state so files whose content did not change stay quiet.
+fn collect_watch_events(
+    files: &mut HashMap<PathBuf, WatchedFile>,
+    events: &[DebouncedEvent],
+) -> Vec<FileWatchEvent> {
+    let mut touched: Vec<&PathBuf> = Vec::new();
+    let renamed_to: HashMap<&PathBuf, &PathBuf> =
+        collect_renames(events).completed.into_values().collect();
+
+    for event in events {
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+
+        for path in &event.paths {
+            if files.contains_key(path) && !touched.contains(&path) {
+                touched.push(path);
+            }
+        }
+    }
+
+    let mut watch_events = Vec::new();
+
+    for key in touched {
+        let Some(file) = files.get_mut(key) else {
+            continue;
+        };
+
+        let exists = key.exists();
+        let bytes = if exists { fs::read(key).ok() } else { None };
+        let hash = bytes.as_deref().map(content_hash);
+        let id = if exists { get_file_id(key).ok() } else { None };
+        let content = bytes.filter(|_| file.include_content).map(decode_content);
+
+        let mut old_path = None;
+        let mut path = file.path.clone();
+
+        let kind = if !exists {
+            match renamed_to.get(key) {
+                Some(to) if file.exists => {
+                    old_path = Some(file.path.clone());
+                    path = renamed_path(&file.path, key, to);
+                    Some("renamed")
+                }
+                _ => file.exists.then_some("removed"),
+            }
+        } else if !file.exists {
+            Some("modified")
+        } else if hash == file.hash {
+            None
+        } else if id != file.id {
+            Some("replaced")
+        } else {
+            Some("modified")
+        };
+
+        let diff = match (kind, &file.content, &content) {
+            (Some("modified" | "replaced"), Some(old), Some(new)) => Some(line_diff(old, new)),
+            _ => None,
+        };
+
+        file.exists = exists;
+        file.hash = hash.clone();
+        file.id = id;
+        file.content = content.clone();
+
+        if let Some(kind) = kind {
+            watch_events.push(FileWatchEvent {
+                path,
+                kind: kind.to_string(),
+                old_path,
+                hash,
+                content,
+                diff,
+            });
+        }
+    }
+
+    watch_events
+}
+
+/// Express a rename target in the same form as the path the frontend watched,
+/// which may differ from the canonical location notifications report.
+fn renamed_path(watched: &IpcPath, key: &Path, to: &Path) -> IpcPath {
+    match to.file_name() {
+        Some(name) if to.parent() == key.parent() => {
+            IpcPath::from(watched.to_path_buf().with_file_name(name))
+        }
+        _ => IpcPath::from(to),
+    }
+}
+
+/// Recursive watcher that keeps a workspace's file tree up to date.
+pub struct WorkspaceWatcher {
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+    show_ignored: bool,
+}
+
+impl WorkspaceWatcher {
+    fn new(app: AppHandle, root: &Path, show_ignored: bool) -> Result<Self, CommandError> {
+        let root_path = root.to_path_buf();
+        let canonical_root = fs::canonicalize(&root_path)
+            .map_err(|e| CommandError::io(&root_path, "Failed to resolve workspace root", e))?;
+        let watched_root = canonical_root.clone();
+
+        let mut debouncer = new_debouncer(
+            DEBOUNCE_TIMEOUT,
+            None,
+            move |result: DebounceEventResult| {
+                let Ok(events) = result else {
+                    return;
+                };
+
+                // Read afresh for every batch so edits to ignore files take effect.
+                let mut listing =
+                    DirectoryListing::new(&root_path, show_ignored, DirectorySort::default());
+                let tree_events =
+                    collect_workspace_events(&root_path, &canonical_root, &events, &mut listing);
+                if !tree_events.is_empty() {
+                    invalidate_quick_open(&app.state::<QuickOpenRegistry>(), &root_path);
+                }
+
+                for event in tree_events {
+                    let _ = app.emit("workspace-watch-event", event);
+                }
+
+                let mut rules = IgnoreRules::new(&root_path, false);
+                let changed = changed_workspace_paths(&root_path, &canonical_root, &events, &mut rules);
+                if !changed.is_empty() {
+                    update_indexed_paths(&app.state::<SearchIndexRegistry>(), &root_path, &changed);
+                }
+            },
+        )
+        .map_err(|e| format!("Failed to start workspace watcher: {}", e))?;
+
+        debouncer
+            .watch(&watched_root, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch workspace: {}", e))?;
+
+        Ok(Self {
+            _debouncer: debouncer,
+            show_ignored,
+        })
+    }
+}
+
+/// Map a notification path back onto the workspace root the frontend opened,
+/// skipping anything outside it or inside a hidden file or directory.
+fn workspace_path(root: &Path, canonical_root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path
+        .strip_prefix(canonical_root)
+        .or_else(|_| path.strip_prefix(root))
+        .ok()?;
+
+    if relative.as_os_str().is_empty() || relative.iter().any(|part| is_hidden(Path::new(part))) {
+        return None;
+    }
+
+    Some(root.join(relative))
+}
+
+/// Every non-ignored workspace path touched by a batch of notifications, for re-indexing.
+fn changed_workspace_paths(
+    root: &Path,
+    canonical_root: &Path,
+    events: &[DebouncedEvent],
+    rules: &mut IgnoreRules,
+) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = Vec::new();
+
+    for event in events {
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+
+        for path in &event.paths {
+            if let Some(path) = workspace_path(root, canonical_root, path) {
+                if !changed.contains(&path) && !rules.is_ignored(&path, path.is_dir()) {
+                    changed.push(path);
+                }
+            }
+        }
+    }
+
+    changed
+}
+
+/// Build the tree entry for a path that appeared in the workspace.
+fn workspace_entry(path: &Path, ignored: bool, listing: &mut DirectoryListing) -> Option<FileEntry> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(build_file_entry(path, &metadata, ignored, 0, 0, listing))
+}
+
+/// Translate a debounced batch of notifications into tree-diff events, leaving
+/// out paths the ignore rules hide. Renames, including moves between watched
+/// directories, become a single `renamed` event.
+fn collect_workspace_events(
+    root: &Path,
+    canonical_root: &Path,
+    events: &[DebouncedEvent],
+    listing: &mut DirectoryListing,
+) -> Vec<WorkspaceWatchEvent> {
+    let root_path = IpcPath::from(root);
+    let mut tree_events = Vec::new();
+
+    let added = |(path, ignored): (PathBuf, bool), listing: &mut DirectoryListing| {
+        let entry = workspace_entry(&path, ignored, listing)?;
+        Some(WorkspaceWatchEvent {
+            root: root_path.clone(),
+            kind: "added".to_string(),
+            path: entry.path.clone(),
+            old_path: None,
+            entry: Some(entry),
+        })
+    };
+    let removed = |(path, _): (PathBuf, bool)| WorkspaceWatchEvent {
+        root: root_path.clone(),
+        kind: "removed".to_string(),
+        path: IpcPath::from(&path),
+        old_path: None,
+        entry: None,
+    };
+
+    let listed = |path: &PathBuf, listing: &mut DirectoryListing| {
+        let path = workspace_path(root, canonical_root, path)?;
+        let ignored = listing.rules.listed(&path, path.is_dir())?;
+        Some((path, ignored))
+    };
+    let renames = collect_renames(events);
+
+    for (index, event) in events.iter().enumerate() {
+        if renames.consumed.contains(&index) {
+            continue;
+        }
+
+        if let Some((from, to)) = renames.completed.get(&index) {
+            match (listed(from, listing), listed(to, listing)) {
+                (Some((from, _)), Some((to, ignored))) => {
+                    if let Some(entry) = workspace_entry(&to, ignored, listing) {
+                        tree_events.push(WorkspaceWatchEvent {
+                            root: root_path.clone(),
+                            kind: "renamed".to_string(),
+                            path: entry.path.clone(),
+                            old_path: Some(IpcPath::from(&from)),
+                            entry: Some(entry),
+                        });
+                    }
+                }
+                (Some(from), None) => tree_events.push(removed(from)),
+                (None, Some(to)) => tree_events.extend(added(to, listing)),
+                (None, None) => {}
+            }
+            continue;
+        }
+
+        let paths: Vec<Option<(PathBuf, bool)>> =
+            event.paths.iter().map(|path| listed(path, listing)).collect();
+
+        match (event.kind, paths.as_slice()) {
+            (EventKind::Create(_), [Some(path)])
+            | (EventKind::Modify(ModifyKind::Name(RenameMode::To)), [Some(path)]) => {
+                tree_events.extend(added(path.clone(), listing));
+            }
+            (EventKind::Remove(_), [Some(path)])
+            | (EventKind::Modify(ModifyKind::Name(RenameMode::From)), [Some(path)]) => {
+                tree_events.push(removed(path.clone()));
+            }
+            // Backends that cannot tell which side of a rename they saw.
+            (EventKind::Modify(ModifyKind::Name(_)), [Some(path)]) => {
+                if path.0.exists() {
+                    tree_events.extend(added(path.clone(), listing));
+                } else {
+                    tree_events.push(removed(path.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tree_events
+}
+
+/// Start watching a file for external changes.
+///
+/// With `include_content`, events carry the file's new content and a line diff
+/// so the frontend does not have to read it again.
+#[tauri::command]
+pub async fn start_file_watch(
+    app: AppHandle,
+    state: State<'_, FileWatchRegistry>,
+    path: IpcPath,
+    include_content: Option<bool>,
+) -> Result<(), CommandError> {
+    let file_path = path.to_path_buf();
+    if file_path.as_os_str().is_empty() {
+        return Err(CommandError::Other("Path is required".to_string()));
+    }
+    check_access(&app.state::<WorkspaceAccess>(), &file_path)?;
+
+    if !file_path.exists() {
+        return Err(CommandError::NotFound(path));
+    }
+
+    let mut watcher = state
+        .watcher
+        .lock()
+        .map_err(|_| "Failed to lock watcher registry")?;
+
+    if watcher.is_none() {
+        *watcher = Some(FileWatcher::new(app)?);
+    }
+
+    match watcher.as_mut() {
+        Some(watcher) => watcher.watch(&path, include_content.unwrap_or(false)),
+        None => Ok(()),
+    }
+}
+
+/// Stop watching a single file.
+#[tauri::command]
+pub async fn stop_file_watch(
+    state: State<'_, FileWatchRegistry>,
+    path: IpcPath,
+) -> Result<(), CommandError> {
+    let mut watcher = state
+        .watcher
+        .lock()
+        .map_err(|_| "Failed to lock watcher registry")?;
+
+    match watcher.as_mut() {
+        Some(watcher) => watcher.unwatch(&path),
+        None => Ok(()),
+    }
+}
+
+/// Stop watching all files.
+#[tauri::command]
+pub async fn stop_all_file_watches(
+    state: State<'_, FileWatchRegistry>,
+) -> Result<(), CommandError> {
+    let mut watcher = state
+        .watcher
+        .lock()
+        .map_err(|_| "Failed to lock watcher registry")?;
+
+    match watcher.as_mut() {
+        Some(watcher) => watcher.unwatch_all(),
+        None => Ok(()),
+    }
+}
+
+/// Start watching a workspace folder recursively for tree changes.
+///
+/// Changes to ignored items are only reported when `show_ignored` is set, which
+/// defaults to the workspace's saved setting; calling again with a different
+/// setting restarts the watcher.
+#[tauri::command]
+pub async fn start_workspace_watch(
+    app: AppHandle,
+    state: State<'_, FileWatchRegistry>,
+    root: IpcPath,
+    show_ignored: Option<bool>,
+) -> Result<(), CommandError> {
+    let root = root.to_path_buf();
+    check_access(&app.state::<WorkspaceAccess>(), &root)?;
+    let show_ignored = show_ignored.unwrap_or_else(|| settings_for(&app, &root).show_ignored);
+    if !root.is_dir() {
+        return Err(CommandError::Other(format!("{} is not a directory", root.display())));
+    }
+
+    let mut workspaces = state
+        .workspaces
+        .lock()
+        .map_err(|_| "Failed to lock watcher registry")?;
+
+    if workspaces
+        .get(&root)
+        .is_some_and(|watcher| watcher.show_ignored == show_ignored)
+    {
+        return Ok(());
+    }
+
+    let watcher = WorkspaceWatcher::new(app, &root, show_ignored)?;
+    workspaces.insert(root, watcher);
+
+    Ok(())
+}
+
+/// Stop watching a workspace folder.
+#[tauri::command]
+pub async fn stop_workspace_watch(
+    state: State<'_, FileWatchRegistry>,
+    root: IpcPath,
+) -> Result<(), CommandError> {
+    state
+        .workspaces
+        .lock()
+        .map_err(|_| "Failed to lock watcher registry")?
+        .remove(&root.to_path_buf());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        collect_watch_events, collect_workspace_events, line_diff, watch_key, DiffHunk,
+        WatchedFile,
+    };
+    use crate::commands::file::{content_hash, DirectoryListing, DirectorySort};
+    use crate::commands::paths::IpcPath;
+    use crate::commands::test_support::make_temp_dir;
+    use notify_debouncer_full::notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+    use notify_debouncer_full::notify::{Event, EventKind};
+    use notify_debouncer_full::DebouncedEvent;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::time::Instant;
+
+    fn debounced(kind: EventKind, path: &Path) -> DebouncedEvent {
+        DebouncedEvent::new(Event::new(kind).add_path(path.to_path_buf()), Instant::now())
+    }
+
+    #[test]
+    fn collect_watch_events_reports_removal_once_and_ignores_unwatched_paths() {
+        let root = make_temp_dir("watch-collect");
+        let file_path = root.join("note.md");
+        fs::write(&file_path, "content").expect("failed to write watched file");
+
+        let (_, key) = watch_key(&file_path).expect("watch key should resolve");
+        let mut files = HashMap::new();
+        files.insert(key.clone(), WatchedFile::read(&IpcPath::from(Path::new("note.md")), &key, false));
+
+        let unchanged = collect_watch_events(
+            &mut files,
+            &[debounced(EventKind::Modify(ModifyKind::Any), &key)],
+        );
+        assert!(unchanged.is_empty());
+
+        fs::remove_file(&file_path).expect("failed to remove watched file");
+        let other = root.join("other.md");
+        let events = collect_watch_events(
+            &mut files,
+            &[
+                debounced(EventKind::Remove(RemoveKind::File), &key),
+                debounced(EventKind::Remove(RemoveKind::File), &key),
+                debounced(EventKind::Create(CreateKind::File), &other),
+            ],
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].path.display(), "note.md");
+        assert_eq!(events[0].kind, "removed");
+
+        fs::write(&file_path, "recreated").expect("failed to recreate watched file");
+        let events = collect_watch_events(
+            &mut files,
+            &[debounced(EventKind::Create(CreateKind::File), &key)],
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "modified");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn collect_workspace_events_maps_tree_changes_and_skips_hidden_and_ignored_paths() {
+        let root = make_temp_dir("watch-workspace");
+        fs::create_dir_all(root.join("notes")).expect("failed to create notes folder");
+        fs::write(root.join("notes").join("renamed.md"), "moved").expect("failed to write file");
+        fs::write(root.join("added.md"), "new").expect("failed to write file");
+        fs::write(root.join(".gitignore"), "*.log\n").expect("failed to write gitignore");
+        fs::write(root.join("debug.log"), "log").expect("failed to write ignored file");
+
+        let rename = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(root.join("old.md"))
+            .add_path(root.join("notes").join("renamed.md"));
+
+        let events = collect_workspace_events(
+            &root,
+            &root,
+            &[
+                debounced(EventKind::Create(CreateKind::File), &root.join("added.md")),
+                debounced(EventKind::Create(CreateKind::File), &root.join(".git").join("index")),
+                debounced(EventKind::Create(CreateKind::File), &root.join("debug.log")),
+                debounced(EventKind::Remove(RemoveKind::Folder), &root.join("gone")),
+                DebouncedEvent::new(rename.clone(), Instant::now()),
+            ],
+            &mut DirectoryListing::new(&root, false, DirectorySort::default()),
+        );
+
+        let kinds: Vec<&str> = events.iter().map(|event| event.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["added", "removed", "renamed"]);
+
+        let added = events[0].entry.as_ref().expect("added event should carry an entry");
+        assert_eq!(added.name, "added.md");
+        assert!(added.is_markdown);
+        assert_eq!(events[1].path, IpcPath::from(root.join("gone")));
+        assert!(events[1].entry.is_none());
+        assert_eq!(events[2].old_path, Some(IpcPath::from(root.join("old.md"))));
+        assert_eq!(events[2].path, IpcPath::from(root.join("notes").join("renamed.md")));
+
+        let shown = collect_workspace_events(
+            &root,
+            &root,
+            &[debounced(EventKind::Create(CreateKind::File), &root.join("debug.log"))],
+            &mut DirectoryListing::new(&root, true, DirectorySort::default()),
+        );
+        assert_eq!(shown.len(), 1);
+        assert!(shown[0].entry.as_ref().is_some_and(|entry| entry.ignored));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn collect_watch_events_suppresses_echoes_of_recorded_writes() {
+        let root = make_temp_dir("watch-echo");
+        let file_path = root.join("note.md");
+        fs::write(&file_path, "before").expect("failed to write watched file");
+
+        let (_, key) = watch_key(&file_path).expect("watch key should resolve");
+        let mut files = HashMap::new();
+        files.insert(key.clone(), WatchedFile::read(&IpcPath::from(Path::new("note.md")), &key, false));
+
+        fs::write(&file_path, "saved by kea").expect("failed to write file");
+        files.get_mut(&key).expect("file should be watched").hash =
+            Some(content_hash(b"saved by kea"));
+        let echoed = collect_watch_events(
+            &mut files,
+            &[debounced(EventKind::Modify(ModifyKind::Any), &key)],
+        );
+        assert!(echoed.is_empty());
+
+        fs::write(&file_path, "edited elsewhere").expect("failed to write file");
+        let external = collect_watch_events(
+            &mut files,
+            &[debounced(EventKind::Modify(ModifyKind::Any), &key)],
+        );
+        assert_eq!(external.len(), 1);
+        assert_eq!(external[0].kind, "modified");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn collect_watch_events_reports_renames_and_replacements() {
+        let root = make_temp_dir("watch-rename");
+        let file_path = root.join("note.md");
+        let swapped_path = root.join("swapped.md");
+        fs::write(&file_path, "original").expect("failed to write watched file");
+        fs::write(&swapped_path, "original").expect("failed to write swapped file");
+
+        let (_, key) = watch_key(&file_path).expect("watch key should resolve");
+        let (_, swapped_key) = watch_key(&swapped_path).expect("watch key should resolve");
+        let watched = IpcPath::from(&file_path);
+        let mut files = HashMap::new();
+        files.insert(key.clone(), WatchedFile::read(&watched, &key, false));
+        files.insert(swapped_key.clone(), WatchedFile::read(&IpcPath::from(Path::new("swapped.md")), &swapped_key, false));
+
+        let temp_path = root.join(".swapped.md.tmp");
+        fs::write(&temp_path, "rewritten").expect("failed to write temp file");
+        fs::rename(&temp_path, &swapped_path).expect("failed to swap file into place");
+        let renamed_path = root.join("renamed.md");
+        fs::rename(&file_path, &renamed_path).expect("failed to rename watched file");
+
+        let rename = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(key.clone())
+            .add_path(key.with_file_name("renamed.md"));
+        let events = collect_watch_events(
+            &mut files,
+            &[
+                DebouncedEvent::new(rename, Instant::now()),
+                debounced(EventKind::Create(CreateKind::File), &swapped_key),
+            ],
+        );
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, "renamed");
+        assert_eq!(events[0].old_path, Some(watched));
+        assert_eq!(events[0].path, IpcPath::from(renamed_path));
+        assert_eq!(events[1].kind, "replaced");
+        assert_eq!(events[1].path.display(), "swapped.md");
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn moves_between_directories_pair_rename_halves_by_tracker() {
+        let root = make_temp_dir("watch-move");
+        fs::create_dir_all(root.join("a")).expect("failed to create source folder");
+        fs::create_dir_all(root.join("b")).expect("failed to create target folder");
+        let file_path = root.join("a").join("note.md");
+        fs::write(&file_path, "moved").expect("failed to write watched file");
+
+        let (_, key) = watch_key(&file_path).expect("watch key should resolve");
+        let watched = IpcPath::from(&file_path);
+        let mut files = HashMap::new();
+        files.insert(key.clone(), WatchedFile::read(&watched, &key, false));
+
+        let moved_path = root.join("b").join("note.md");
+        fs::rename(&file_path, &moved_path).expect("failed to move watched file");
+        let (_, moved_key) = watch_key(&moved_path).expect("watch key should resolve");
+
+        let half = |mode, path: &Path| {
+            let event = Event::new(EventKind::Modify(ModifyKind::Name(mode)))
+                .add_path(path.to_path_buf())
+                .set_tracker(7);
+            DebouncedEvent::new(event, Instant::now())
+        };
+        let both = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(key.clone())
+            .add_path(moved_key.clone())
+            .set_tracker(7);
+        let batch = [
+            half(RenameMode::From, &key),
+            half(RenameMode::To, &moved_key),
+            DebouncedEvent::new(both, Instant::now()),
+        ];
+
+        let events = collect_watch_events(&mut files, &batch);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "renamed");
+        assert_eq!(events[0].old_path, Some(watched));
+        assert_eq!(events[0].path, IpcPath::from(&moved_key));
+
+        let tree_events = collect_workspace_events(
+            &root,
+            &root,
+            &batch[..2],
+            &mut DirectoryListing::new(&root, false, DirectorySort::default()),
+        );
+        assert_eq!(tree_events.len(), 1);
+        assert_eq!(tree_events[0].kind, "renamed");
+        assert_eq!(tree_events[0].old_path, Some(IpcPath::from(&key)));
+        assert_eq!(tree_events[0].path, IpcPath::from(&moved_key));
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn line_diff_reports_changed_line_ranges() {
+        let hunks = line_diff("one\ntwo\nthree\n", "one\n2\nthree\nfour\n");
+
+        assert_eq!(
+            hunks,
+            vec![
+                DiffHunk {
+                    old_start: 1,
+                    old_len: 1,
+                    new_start: 1,
+                    new_lines: vec!["2\n".to_string()],
+                },
+                DiffHunk {
+                    old_start: 3,
+                    old_len: 0,
+                    new_start: 3,
+                    new_lines: vec!["four\n".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn collect_watch_events_includes_content_and_diff_when_requested() {
+        let root = make_temp_dir("watch-content");
+        let file_path = root.join("note.md");
+        fs::write(&file_path, "# Title\nbody\n").expect("failed to write watched file");
+
+        let (_, key) = watch_key(&file_path).expect("watch key should resolve");
+        let mut files = HashMap::new();
+        files.insert(key.clone(), WatchedFile::read(&IpcPath::from(Path::new("note.md")), &key, true));
+
+        fs::write(&file_path, "# Title\nnew body\n").expect("failed to write file");
+        let events = collect_watch_events(
+            &mut files,
+            &[debounced(EventKind::Modify(ModifyKind::Any), &key)],
+        );
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].content.as_deref(), Some("# Title\nnew body\n"));
+        assert_eq!(events[0].hash, Some(content_hash(b"# Title\nnew body\n")));
+        let diff = events[0].diff.as_ref().expect("diff should be included");
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].old_start, 1);
+        assert_eq!(diff[0].new_lines, vec!["new body\n".to_string()]);
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -4,19 +4,129 @@ use tauri::{
     Emitter,
 };
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 
 mod commands;
 
+use commands::history::HistoryStore;
+use commands::index::SearchIndex;
+use commands::journal::FileOperationHistory;
+use commands::quick_open::QuickOpenEntry;
+use commands::recovery::BufferJournal;
+use commands::settings::WorkspaceConfig;
+use commands::watch::{FileWatcher, WorkspaceWatcher};
+
 pub struct FileWatchRegistry {
-    pub watchers: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    pub watcher: Mutex<Option<FileWatcher>>,
+    pub workspaces: Mutex<HashMap<PathBuf, WorkspaceWatcher>>,
 }
 
 impl Default for FileWatchRegistry {
     fn default() -> Self {
         Self {
-            watchers: Mutex::new(HashMap::new()),
+            watcher: Mutex::new(None),
+            workspaces: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+pub struct FileOperationJournal {
+    pub history: Mutex<FileOperationHistory>,
+}
+
+impl Default for FileOperationJournal {
+    fn default() -> Self {
+        Self {
+            history: Mutex::new(FileOperationHistory::default()),
+        }
+    }
+}
+
+pub struct SearchIndexRegistry {
+    pub indexes: Mutex<HashMap<PathBuf, SearchIndex>>,
+}
+
+impl Default for SearchIndexRegistry {
+    fn default() -> Self {
+        Self {
+            indexes: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+pub struct QuickOpenRegistry {
+    pub workspaces: Mutex<HashMap<PathBuf, Vec<QuickOpenEntry>>>,
+}
+
+impl Default for QuickOpenRegistry {
+    fn default() -> Self {
+        Self {
+            workspaces: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+pub struct HistoryRegistry {
+    pub stores: Mutex<HashMap<PathBuf, HistoryStore>>,
+}
+
+impl Default for HistoryRegistry {
+    fn default() -> Self {
+        Self {
+            stores: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+pub struct RecoveryJournal {
+    pub journal: Mutex<Option<BufferJournal>>,
+}
+
+impl Default for RecoveryJournal {
+    fn default() -> Self {
+        Self {
+            journal: Mutex::new(None),
+        }
+    }
+}
+
+/// Settings chosen per workspace, loaded on first use.
+pub struct WorkspaceSettings {
+    pub workspaces: Mutex<Option<HashMap<PathBuf, WorkspaceConfig>>>,
+}
+
+impl Default for WorkspaceSettings {
+    fn default() -> Self {
+        Self {
+            workspaces: Mutex::new(None),
+        }
+    }
+}
+
+/// Folders and files the user opened through a native dialog. File commands
+/// refuse paths outside them.
+pub struct WorkspaceAccess {
+    pub granted: Mutex<Vec<PathBuf>>,
+}
+
+impl Default for WorkspaceAccess {
+    fn default() -> Self {
+        Self {
+            granted: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+pub struct SearchRegistry {
+    pub searches: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Default for SearchRegistry {
+    fn default() -> Self {
+        Self {
+            searches: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -26,9 +136,18 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_fs::init())
         .manage(FileWatchRegistry::default())
+        .manage(FileOperationJournal::default())
+        .manage(SearchRegistry::default())
+        .manage(SearchIndexRegistry::default())
+        .manage(QuickOpenRegistry::default())
+        .manage(WorkspaceAccess::default())
+        .manage(HistoryRegistry::default())
+        .manage(RecoveryJournal::default())
+        .manage(WorkspaceSettings::default())
         .setup(|app| {
+            commands::sandbox::restore_access(app.handle());
+
             let window = app.get_webview_window("main").unwrap();
 
             #[cfg(target_os = "macos")]
@@ -201,14 +320,41 @@ pub fn run() {
             commands::file::open_folder_dialog,
             commands::file::read_directory,
             commands::file::read_file,
+            commands::file::read_linked_image,
             commands::file::create_file,
             commands::file::create_folder,
             commands::file::rename_item,
             commands::file::delete_item,
+            commands::file::restore_deleted_item,
             commands::file::move_item,
-            commands::file::start_file_watch,
-            commands::file::stop_file_watch,
-            commands::file::stop_all_file_watches,
+            commands::journal::undo_file_operation,
+            commands::journal::redo_file_operation,
+            commands::history::list_file_history,
+            commands::history::read_file_version,
+            commands::history::diff_file_versions,
+            commands::history::restore_file_version,
+            commands::recovery::journal_buffer,
+            commands::recovery::discard_buffer,
+            commands::recovery::list_recoverable_buffers,
+            commands::backup::get_workspace_backups,
+            commands::backup::set_workspace_backups,
+            commands::settings::get_workspace_show_ignored,
+            commands::settings::set_workspace_show_ignored,
+            commands::settings::get_workspace_save_format,
+            commands::settings::set_workspace_save_format,
+            commands::search::search_workspace,
+            commands::search::cancel_workspace_search,
+            commands::search::preview_workspace_replace,
+            commands::search::apply_workspace_replace,
+            commands::index::open_search_index,
+            commands::index::query_search_index,
+            commands::index::close_search_index,
+            commands::quick_open::quick_open_query,
+            commands::watch::start_file_watch,
+            commands::watch::stop_file_watch,
+            commands::watch::stop_all_file_watches,
+            commands::watch::start_workspace_watch,
+            commands::watch::stop_workspace_watch,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
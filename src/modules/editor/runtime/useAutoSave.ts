@@ -13,6 +13,11 @@ export function useAutoSave(intervalMs = 2000) {
       try {
         saveStatus.value = 'saving'
         await documentStore.saveFile()
+        if (documentStore.externalChange?.saveConflict) {
+          saveStatus.value = 'error'
+          lastSaveError.value = 'File changed on disk'
+          return
+        }
         saveStatus.value = 'saved'
         lastSaveError.value = null
         
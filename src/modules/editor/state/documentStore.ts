@@ -1,5 +1,8 @@
 import { defineStore } from 'pinia'
 import { tauriDocumentStoragePort } from '../../../platform/tauri/documentStorage'
+import { isCommandError } from '../../../platform/tauri/commandError'
+import { isSameOrWithin, pathName, replacePathPrefix } from '../../../platform/tauri/ipcPath'
+import type { OpenedDocumentData, SaveOutcomeData } from '../../../platform/tauri/documentStorage.types'
 import type { DocumentCommand } from './documentCommands'
 import { dispatchDocumentCommand } from './dispatchDocumentCommand'
 import type { EditorMode } from '../editorCommands'
@@ -11,8 +14,15 @@ export interface OpenDocument {
   content: string
   savedContent: string
   isDirty: boolean
+  /** Modification time of the file when it was last read or saved. */
+  modified?: number | null
+  /** Hash of the file when it was last read or saved; saves are refused if the disk no longer matches. */
+  hash?: string
 }
 
+/** What the editor knows about a document's file on disk. */
+type DiskState = Pick<OpenedDocumentData, 'modified' | 'hash'>
+
 interface RecentFile {
   path: string
   name: string
@@ -24,6 +34,10 @@ interface ExternalChangeAlert {
   path: string
   diskContent: string
   detectedAt: number
+  /** Hash of `diskContent`, so keeping the local version can overwrite it. */
+  diskHash?: string
+  /** A save was refused because of this change, so the local edits are not on disk. */
+  saveConflict?: boolean
 }
 
 function runDocumentCommand<TResult>(command: DocumentCommand, apply: () => TResult): TResult {
@@ -117,7 +131,7 @@ export const useDocumentStore = defineStore('document', {
     /**
      * Open a file and load its content (supports multiple tabs)
      */
-    async openFile(path: string, content: string, name: string): Promise<string> {
+    async openFile(path: string, content: string, name: string, disk: DiskState = {}): Promise<string> {
       // Check if already open
       const existing = this.findDocumentByPath(path)
       if (existing) {
@@ -134,6 +148,8 @@ export const useDocumentStore = defineStore('document', {
         content,
         savedContent: content,
         isDirty: false,
+        modified: disk.modified,
+        hash: disk.hash,
       }
 
       return runDocumentCommand(
@@ -170,7 +186,7 @@ export const useDocumentStore = defineStore('document', {
       try {
         this.isLoading = true
         const result = await documentStoragePort.readFile(path)
-        return await this.openFile(result.path, result.content, result.name)
+        return await this.openFile(result.path, result.content, result.name, result)
       } catch (error) {
         console.error('Failed to open file:', error)
         throw error
@@ -343,7 +359,11 @@ export const useDocumentStore = defineStore('document', {
       try {
         this.isSaving = true
 
-        await documentStoragePort.saveMarkdownFile(doc.path, doc.content)
+        const outcome = await documentStoragePort.saveMarkdownFile(doc.path, doc.content, doc.hash)
+        if (outcome.status === 'conflict') {
+          this.reportSaveConflict(doc, outcome)
+          return false
+        }
 
         return runDocumentCommand(
           {
@@ -356,6 +376,8 @@ export const useDocumentStore = defineStore('document', {
           () => {
             doc.savedContent = doc.content
             doc.isDirty = false
+            doc.modified = outcome.modified
+            doc.hash = outcome.hash
             this.externalChange = null
             delete this.ignoredExternalChanges[doc.path]
             this.lastSaveTime = Date.now()
@@ -379,7 +401,11 @@ export const useDocumentStore = defineStore('document', {
       
       for (const doc of unsaved) {
         try {
-          await documentStoragePort.saveMarkdownFile(doc.path, doc.content)
+          const outcome = await documentStoragePort.saveMarkdownFile(doc.path, doc.content, doc.hash)
+          if (outcome.status === 'conflict') {
+            this.reportSaveConflict(doc, outcome)
+            return false
+          }
 
           runDocumentCommand(
             {
@@ -392,6 +418,8 @@ export const useDocumentStore = defineStore('document', {
             () => {
               doc.savedContent = doc.content
               doc.isDirty = false
+              doc.modified = outcome.modified
+              doc.hash = outcome.hash
 
               if (this.externalChange?.documentId === doc.id) {
                 this.externalChange = null
@@ -435,6 +463,8 @@ export const useDocumentStore = defineStore('document', {
             doc.name = result.name
             doc.savedContent = doc.content
             doc.isDirty = false
+            doc.modified = result.modified
+            doc.hash = result.hash
             this.externalChange = null
             delete this.ignoredExternalChanges[result.path]
             this.lastSaveTime = Date.now()
@@ -461,11 +491,11 @@ export const useDocumentStore = defineStore('document', {
 
         const result = await documentStoragePort.openMarkdownFile()
 
-        await this.openFile(result.path, result.content, result.name)
+        await this.openFile(result.path, result.content, result.name, result)
 
         return true
       } catch (error) {
-        if (error !== 'No file selected') {
+        if (!isCommandError(error, 'cancelled')) {
           console.error('Failed to open file:', error)
         }
         return false
@@ -505,6 +535,22 @@ export const useDocumentStore = defineStore('document', {
       )
     },
 
+    /**
+     * Show the disk version of a document whose save was refused because the
+     * file changed since it was loaded
+     */
+    reportSaveConflict(doc: OpenDocument, outcome: Extract<SaveOutcomeData, { status: 'conflict' }>): void {
+      delete this.ignoredExternalChanges[doc.path]
+      this.externalChange = {
+        documentId: doc.id,
+        path: doc.path,
+        diskContent: outcome.content,
+        detectedAt: Date.now(),
+        diskHash: outcome.hash,
+        saveConflict: true,
+      }
+    },
+
     async checkActiveDocumentExternalChange(): Promise<void> {
       const doc = this.activeDocument
       if (!doc || !doc.path || this.isSaving || this.isLoading) return
@@ -514,7 +560,9 @@ export const useDocumentStore = defineStore('document', {
         const diskContent = result.content
 
         if (diskContent === doc.savedContent) {
-          if (this.externalChange?.documentId === doc.id) {
+          doc.modified = result.modified
+          doc.hash = result.hash
+          if (this.externalChange?.documentId === doc.id && !this.externalChange.saveConflict) {
             this.externalChange = null
           }
           delete this.ignoredExternalChanges[doc.path]
@@ -534,6 +582,8 @@ export const useDocumentStore = defineStore('document', {
               doc.content = diskContent
               doc.savedContent = diskContent
               doc.isDirty = false
+              doc.modified = result.modified
+              doc.hash = result.hash
               this.externalChange = null
               delete this.ignoredExternalChanges[doc.path]
             }
@@ -551,10 +601,10 @@ export const useDocumentStore = defineStore('document', {
           path: doc.path,
           diskContent,
           detectedAt: Date.now(),
+          diskHash: result.hash,
         }
       } catch (error) {
-        const errorMessage = error instanceof Error ? error.message : String(error)
-        if (errorMessage.includes('does not exist')) {
+        if (isCommandError(error, 'not_found')) {
           return
         }
 
@@ -584,6 +634,7 @@ export const useDocumentStore = defineStore('document', {
           doc.content = change.diskContent
           doc.savedContent = change.diskContent
           doc.isDirty = false
+          doc.hash = change.diskHash
           this.externalChange = null
           delete this.ignoredExternalChanges[doc.path]
         }
@@ -596,6 +647,12 @@ export const useDocumentStore = defineStore('document', {
       const change = this.externalChange
       if (!change) return false
 
+      // The next save replaces the disk version instead of conflicting with it.
+      const doc = this.openDocuments.find(d => d.id === change.documentId)
+      if (doc && change.diskHash) {
+        doc.hash = change.diskHash
+      }
+
       this.ignoredExternalChanges[change.path] = change.diskContent
       this.externalChange = null
       return true
@@ -604,12 +661,11 @@ export const useDocumentStore = defineStore('document', {
     pathMatchesRename(path: string, oldPath: string, isDirectory: boolean): boolean {
       if (path === oldPath) return true
       if (!isDirectory) return false
-      return path.startsWith(`${oldPath}/`) || path.startsWith(`${oldPath}\\`)
+      return isSameOrWithin(path, oldPath)
     },
 
     replacePathPrefix(path: string, oldPath: string, newPath: string): string {
-      if (path === oldPath) return newPath
-      return `${newPath}${path.slice(oldPath.length)}`
+      return replacePathPrefix(path, oldPath, newPath)
     },
 
     updatePathsAfterRename(oldPath: string, newPath: string, isDirectory: boolean): void {
@@ -619,7 +675,7 @@ export const useDocumentStore = defineStore('document', {
 
       affectedDocuments.forEach(doc => {
         doc.path = this.replacePathPrefix(doc.path, oldPath, newPath)
-        const updatedName = doc.path.split(/[\\/]/).pop()
+        const updatedName = pathName(doc.path)
         if (updatedName) {
           doc.name = updatedName
         }
@@ -649,7 +705,7 @@ export const useDocumentStore = defineStore('document', {
           return {
             ...file,
             path: updatedPath,
-            name: updatedPath.split(/[\\/]/).pop() || file.name,
+            name: pathName(updatedPath) || file.name,
           }
         })
         .filter(file => {
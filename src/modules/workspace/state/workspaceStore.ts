@@ -1,11 +1,20 @@
 import { defineStore } from 'pinia'
 import { tauriWorkspacePort } from '../../../platform/tauri/workspaceFs'
+import { isCommandError } from '../../../platform/tauri/commandError'
+import { isSameOrWithin, joinPath, parentPath, pathName, replacePathPrefix } from '../../../platform/tauri/ipcPath'
 
 export interface FileEntry {
   name: string
   path: string
   is_dir: boolean
   is_markdown: boolean
+  ignored?: boolean
+  size?: number
+  created?: number | null
+  modified?: number | null
+  readonly?: boolean
+  symlink_target?: string | null
+  child_count?: number | null
   children?: FileEntry[]
 }
 
@@ -65,7 +74,7 @@ export const useWorkspaceStore = defineStore('workspace', {
         
         return true
       } catch (error) {
-        if (error !== 'No folder selected') {
+        if (!isCommandError(error, 'cancelled')) {
           console.error('Failed to open folder:', error)
         }
         return false
@@ -146,39 +155,24 @@ export const useWorkspaceStore = defineStore('workspace', {
       return null
     },
 
-    buildChildPath(parentPath: string, name: string): string {
-      const separator = parentPath.includes('\\') && !parentPath.includes('/') ? '\\' : '/'
-      return `${parentPath.replace(/[\\/]+$/, '')}${separator}${name}`
+    buildChildPath(parent: string, name: string): string {
+      return joinPath(parent, name)
     },
 
     getParentPath(path: string): string | null {
-      const separatorIndex = Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\'))
-      if (separatorIndex <= 0) {
-        return null
-      }
-
-      return path.slice(0, separatorIndex)
+      return parentPath(path)
     },
 
     getPathName(path: string): string {
-      const separatorIndex = Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\'))
-      return separatorIndex >= 0 ? path.slice(separatorIndex + 1) : path
+      return pathName(path)
     },
 
     pathMatches(path: string, prefix: string): boolean {
-      return path === prefix || path.startsWith(`${prefix}/`) || path.startsWith(`${prefix}\\`)
+      return isSameOrWithin(path, prefix)
     },
 
     replacePathPrefix(path: string, oldPrefix: string, newPrefix: string): string {
-      if (path === oldPrefix) {
-        return newPrefix
-      }
-
-      if (!this.pathMatches(path, oldPrefix)) {
-        return path
-      }
-
-      return `${newPrefix}${path.slice(oldPrefix.length)}`
+      return replacePathPrefix(path, oldPrefix, newPrefix)
     },
 
     updateDescendantPaths(entry: FileEntry, oldPrefix: string, newPrefix: string): void {
@@ -485,7 +479,7 @@ export const useWorkspaceStore = defineStore('workspace', {
         const entries = await this.loadDirectory(savedPath, true)
         
         this.rootPath = savedPath
-        this.rootName = savedPath.split('/').pop() || 'Workspace'
+        this.rootName = pathName(savedPath) || 'Workspace'
         this.entries = entries
         
         return true
@@ -0,0 +1,31 @@
+import type { IpcPathData } from './workspaceFs.types'
+
+/** Error rejected by file commands; branch on `kind`, `message` is English and for logs. */
+export interface CommandErrorData {
+  kind:
+    | 'not_found'
+    | 'already_exists'
+    | 'permission_denied'
+    | 'conflict'
+    | 'empty_overwrite'
+    | 'invalid_name'
+    | 'io'
+    | 'cancelled'
+    | 'out_of_workspace'
+    | 'other'
+  message: string
+  path?: IpcPathData
+  /** OS error code, for `io` errors. */
+  code?: number
+}
+
+export function isCommandError(
+  error: unknown,
+  kind?: CommandErrorData['kind'],
+): error is CommandErrorData {
+  if (typeof error !== 'object' || error === null || !('kind' in error)) {
+    return false
+  }
+
+  return kind === undefined || (error as CommandErrorData).kind === kind
+}
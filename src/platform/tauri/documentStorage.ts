@@ -1,29 +1,39 @@
 import { invoke } from '@tauri-apps/api/core'
+import { toIpcPath, withDisplayPath } from './ipcPath'
 import type {
   DocumentStoragePort,
   OpenedDocumentData,
   SaveDocumentAsResult,
+  SaveOutcomeData,
 } from './documentStorage.types'
 
 export const tauriDocumentStoragePort: DocumentStoragePort = {
-  readFile(path: string): Promise<OpenedDocumentData> {
-    return invoke<OpenedDocumentData>('read_file', { path })
+  readFile(path: string) {
+    return invoke<OpenedDocumentData>('read_file', { path: toIpcPath(path) }).then(withDisplayPath)
   },
 
-  openMarkdownFile(): Promise<OpenedDocumentData> {
-    return invoke<OpenedDocumentData>('open_markdown_file')
+  openMarkdownFile() {
+    return invoke<OpenedDocumentData>('open_markdown_file').then(withDisplayPath)
   },
 
-  saveMarkdownFile(path: string, content: string): Promise<void> {
-    return invoke('save_markdown_file', {
-      path,
+  saveMarkdownFile(path: string, content: string, expectedHash?: string): Promise<SaveOutcomeData> {
+    return invoke<SaveOutcomeData>('save_markdown_file', {
+      path: toIpcPath(path),
       content,
+      expectedHash,
     })
   },
 
-  saveMarkdownFileAs(content: string): Promise<SaveDocumentAsResult> {
+  saveMarkdownFileAs(content: string) {
     return invoke<SaveDocumentAsResult>('save_markdown_file_as', {
       content,
+    }).then(withDisplayPath)
+  },
+
+  readLinkedImage(documentPath: string, target: string): Promise<ArrayBuffer> {
+    return invoke<ArrayBuffer>('read_linked_image', {
+      document: toIpcPath(documentPath),
+      target: toIpcPath(target),
     })
   },
 }
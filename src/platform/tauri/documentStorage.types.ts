@@ -1,17 +1,39 @@
+import type { WithDisplayPath } from './ipcPath'
+import type { IpcPathData } from './workspaceFs.types'
+
 export interface OpenedDocumentData {
-  path: string
+  path: IpcPathData
   content: string
   name: string
+  /** Encoding the file is stored in; saves keep it unless told otherwise. */
+  encoding?: string
+  bom?: boolean
+  /** Line style the file is stored with; saves normalise back to it. */
+  line_ending?: 'lf' | 'crlf' | 'cr'
+  final_newline?: boolean
+  /** Modification time on disk, in milliseconds since the Unix epoch. */
+  modified?: number | null
+  /** Hash of the bytes on disk; sent back on save to detect conflicting edits. */
+  hash?: string
 }
 
 export interface SaveDocumentAsResult {
-  path: string
+  path: IpcPathData
   name: string
+  modified?: number | null
+  hash?: string
 }
 
+/** Result of a save; `conflict` means the file changed on disk and nothing was written. */
+export type SaveOutcomeData =
+  | { status: 'saved'; modified: number | null; hash: string }
+  | { status: 'conflict'; content: string; modified: number | null; hash: string }
+
 export interface DocumentStoragePort {
-  readFile: (path: string) => Promise<OpenedDocumentData>
-  openMarkdownFile: () => Promise<OpenedDocumentData>
-  saveMarkdownFile: (path: string, content: string) => Promise<void>
-  saveMarkdownFileAs: (content: string) => Promise<SaveDocumentAsResult>
+  readFile: (path: string) => Promise<WithDisplayPath<OpenedDocumentData>>
+  openMarkdownFile: () => Promise<WithDisplayPath<OpenedDocumentData>>
+  saveMarkdownFile: (path: string, content: string, expectedHash?: string) => Promise<SaveOutcomeData>
+  saveMarkdownFileAs: (content: string) => Promise<WithDisplayPath<SaveDocumentAsResult>>
+  /** Raw bytes of an image linked from the document at `documentPath`. */
+  readLinkedImage: (documentPath: string, target: string) => Promise<ArrayBuffer>
 }
@@ -1,13 +1,14 @@
 import { invoke } from '@tauri-apps/api/core'
+import { toIpcPath } from './ipcPath'
 import type { FileWatchPort } from './fileWatch.types'
 
 export const tauriFileWatchPort: FileWatchPort = {
   startFileWatch(path: string): Promise<void> {
-    return invoke('start_file_watch', { path })
+    return invoke('start_file_watch', { path: toIpcPath(path) })
   },
 
   stopFileWatch(path: string): Promise<void> {
-    return invoke('stop_file_watch', { path })
+    return invoke('stop_file_watch', { path: toIpcPath(path) })
   },
 
   stopAllFileWatches(): Promise<void> {
@@ -0,0 +1,97 @@
+import type { IpcPathData, RawPathData } from './workspaceFs.types'
+
+/**
+ * Non-UTF-8 paths seen from the backend, by their display form. The frontend
+ * works with display strings only; these let them be sent back intact.
+ */
+const rawPaths = new Map<string, RawPathData>()
+
+/** A backend type with its `path` turned into the display string the frontend works with. */
+export type WithDisplayPath<T extends { path: IpcPathData }> = Omit<T, 'path'> & { path: string }
+
+/** The display string for a path received from the backend. */
+export function fromIpcPath(path: IpcPathData): string {
+  if (typeof path === 'string') {
+    return path
+  }
+
+  rawPaths.set(path.display, path)
+  return path.display
+}
+
+export function withDisplayPath<T extends { path: IpcPathData }>(data: T): WithDisplayPath<T> {
+  return { ...data, path: fromIpcPath(data.path) }
+}
+
+function encodeSuffix(raw: RawPathData, suffix: string): number[] {
+  // Windows paths are sent as UTF-16LE and start with a drive letter or `\\`,
+  // so their second byte is zero; Unix paths are bytes and start with `/`.
+  if (raw.bytes.length >= 2 && raw.bytes[1] === 0) {
+    const bytes: number[] = []
+    for (let i = 0; i < suffix.length; i++) {
+      const unit = suffix.charCodeAt(i)
+      bytes.push(unit & 0xff, unit >> 8)
+    }
+    return bytes
+  }
+
+  return Array.from(new TextEncoder().encode(suffix))
+}
+
+/**
+ * The form to send a display path to the backend in. A path that is, or lies
+ * below, a non-UTF-8 path received earlier keeps that path's raw bytes.
+ */
+export function toIpcPath(path: string): IpcPathData {
+  let base: RawPathData | undefined
+  for (const raw of rawPaths.values()) {
+    if (isSameOrWithin(path, raw.display) && raw.display.length > (base?.display.length ?? -1)) {
+      base = raw
+    }
+  }
+
+  if (!base) {
+    return path
+  }
+
+  return {
+    display: path,
+    bytes: [...base.bytes, ...encodeSuffix(base, path.slice(base.display.length))],
+  }
+}
+
+/** Whether `path` is `prefix` itself or lies below it. */
+export function isSameOrWithin(path: string, prefix: string): boolean {
+  return path === prefix || path.startsWith(`${prefix}/`) || path.startsWith(`${prefix}\\`)
+}
+
+/** `path` with the leading `oldPrefix` replaced by `newPrefix`; other paths are returned unchanged. */
+export function replacePathPrefix(path: string, oldPrefix: string, newPrefix: string): string {
+  if (!isSameOrWithin(path, oldPrefix)) {
+    return path
+  }
+
+  return `${newPrefix}${path.slice(oldPrefix.length)}`
+}
+
+/** The last component of `path`. */
+export function pathName(path: string): string {
+  const separatorIndex = Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\'))
+  return separatorIndex >= 0 ? path.slice(separatorIndex + 1) : path
+}
+
+/** The folder containing `path`, or `null` for a root. */
+export function parentPath(path: string): string | null {
+  const separatorIndex = Math.max(path.lastIndexOf('/'), path.lastIndexOf('\\'))
+  if (separatorIndex <= 0) {
+    return null
+  }
+
+  return path.slice(0, separatorIndex)
+}
+
+/** `name` inside the folder `parent`, using the separator style `parent` already uses. */
+export function joinPath(parent: string, name: string): string {
+  const separator = parent.includes('\\') && !parent.includes('/') ? '\\' : '/'
+  return `${parent.replace(/[\\/]+$/, '')}${separator}${name}`
+}
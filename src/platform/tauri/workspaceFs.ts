@@ -1,46 +1,65 @@
 import { invoke } from '@tauri-apps/api/core'
+import { fromIpcPath, toIpcPath, withDisplayPath } from './ipcPath'
 import type {
   CreatedFileData,
+  DirectoryContentsData,
+  MovedItemData,
+  OpenedFolder,
   OpenedFolderData,
+  WorkspaceEntry,
   WorkspaceFileEntry,
   WorkspacePort,
 } from './workspaceFs.types'
 
+function toWorkspaceEntry(entry: WorkspaceFileEntry): WorkspaceEntry {
+  return {
+    ...entry,
+    path: fromIpcPath(entry.path),
+    children: entry.children?.map(toWorkspaceEntry),
+  }
+}
+
 export const tauriWorkspacePort: WorkspacePort = {
-  openFolderDialog(): Promise<OpenedFolderData> {
-    return invoke<OpenedFolderData>('open_folder_dialog')
+  openFolderDialog(): Promise<OpenedFolder> {
+    return invoke<OpenedFolderData>('open_folder_dialog').then((result) => ({
+      ...result,
+      path: fromIpcPath(result.path),
+      entries: result.entries.map(toWorkspaceEntry),
+    }))
   },
 
-  readDirectory(path: string): Promise<WorkspaceFileEntry[]> {
-    return invoke<WorkspaceFileEntry[]>('read_directory', { path })
+  readDirectory(path: string): Promise<WorkspaceEntry[]> {
+    return invoke<DirectoryContentsData>('read_directory', { path: toIpcPath(path) }).then((result) =>
+      result.entries.map(toWorkspaceEntry)
+    )
   },
 
-  createFile(path: string, content: string): Promise<CreatedFileData> {
+  createFile(path: string, content: string) {
     return invoke<CreatedFileData>('create_file', {
-      path,
+      path: toIpcPath(path),
       content,
-    })
+    }).then(withDisplayPath)
   },
 
-  createFolder(path: string): Promise<WorkspaceFileEntry> {
-    return invoke<WorkspaceFileEntry>('create_folder', { path })
+  createFolder(path: string): Promise<WorkspaceEntry> {
+    return invoke<WorkspaceFileEntry>('create_folder', { path: toIpcPath(path) }).then(toWorkspaceEntry)
   },
 
   renameItem(oldPath: string, newName: string): Promise<string> {
-    return invoke<string>('rename_item', {
-      oldPath,
+    return invoke<MovedItemData>('rename_item', {
+      oldPath: toIpcPath(oldPath),
       newName,
-    })
+    }).then((result) => fromIpcPath(result.path))
   },
 
   deleteItem(path: string): Promise<void> {
-    return invoke('delete_item', { path })
+    return invoke('delete_item', { path: toIpcPath(path) })
   },
 
   moveItem(sourcePath: string, targetDir: string): Promise<string> {
-    return invoke<string>('move_item', {
-      sourcePath,
-      targetDir,
-    })
+    return invoke<MovedItemData>('move_item', {
+      sourcePath: toIpcPath(sourcePath),
+      targetDir: toIpcPath(targetDir),
+    }).then((result) => fromIpcPath(result.path))
   },
 }
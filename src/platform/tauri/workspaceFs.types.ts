@@ -1,28 +1,79 @@
+import type { WithDisplayPath } from './ipcPath'
+
+/** A path the backend could not express as UTF-8, sent as raw bytes plus a readable form. */
+export interface RawPathData {
+  display: string
+  bytes: number[]
+}
+
+/** Paths travel as plain strings unless they are not valid UTF-8. */
+export type IpcPathData = string | RawPathData
+
 export interface WorkspaceFileEntry {
   name: string
-  path: string
+  path: IpcPathData
   is_dir: boolean
   is_markdown: boolean
+  ignored?: boolean
+  size?: number
+  created?: number | null
+  modified?: number | null
+  readonly?: boolean
+  symlink_target?: string | null
+  child_count?: number | null
   children?: WorkspaceFileEntry[]
 }
 
+export interface ListingWarningData {
+  path: IpcPathData
+  kind: 'permission_denied' | 'broken_symlink' | 'symlink_loop' | 'unreadable'
+  message: string
+}
+
 export interface OpenedFolderData {
-  path: string
+  path: IpcPathData
   name: string
   entries: WorkspaceFileEntry[]
+  warnings?: ListingWarningData[]
+}
+
+export interface DirectoryContentsData {
+  entries: WorkspaceFileEntry[]
+  warnings: ListingWarningData[]
 }
 
 export interface CreatedFileData {
-  path: string
+  path: IpcPathData
   content: string
   name: string
 }
 
+export interface LinkUpdateData {
+  path: IpcPathData
+  links: number
+}
+
+export interface MovedItemData {
+  path: IpcPathData
+  updated_files: LinkUpdateData[]
+}
+
+/** A listed entry as the frontend works with it, with display paths throughout. */
+export interface WorkspaceEntry extends Omit<WorkspaceFileEntry, 'path' | 'children'> {
+  path: string
+  children?: WorkspaceEntry[]
+}
+
+export interface OpenedFolder extends Omit<OpenedFolderData, 'path' | 'entries'> {
+  path: string
+  entries: WorkspaceEntry[]
+}
+
 export interface WorkspacePort {
-  openFolderDialog: () => Promise<OpenedFolderData>
-  readDirectory: (path: string) => Promise<WorkspaceFileEntry[]>
-  createFile: (path: string, content: string) => Promise<CreatedFileData>
-  createFolder: (path: string) => Promise<WorkspaceFileEntry>
+  openFolderDialog: () => Promise<OpenedFolder>
+  readDirectory: (path: string) => Promise<WorkspaceEntry[]>
+  createFile: (path: string, content: string) => Promise<WithDisplayPath<CreatedFileData>>
+  createFolder: (path: string) => Promise<WorkspaceEntry>
   renameItem: (oldPath: string, newName: string) => Promise<string>
   deleteItem: (path: string) => Promise<void>
   moveItem: (sourcePath: string, targetDir: string) => Promise<string>
@@ -6,6 +6,7 @@ const documentStoragePortMock = vi.hoisted(() => ({
   openMarkdownFile: vi.fn(),
   saveMarkdownFile: vi.fn(),
   saveMarkdownFileAs: vi.fn(),
+  readLinkedImage: vi.fn(),
 }))
 
 vi.mock('../../src/platform/tauri/documentStorage', () => ({
@@ -37,7 +38,7 @@ describe('documentStore actions', () => {
   })
 
   it('saves active file and clears dirty state', async () => {
-    documentStoragePortMock.saveMarkdownFile.mockResolvedValue(undefined)
+    documentStoragePortMock.saveMarkdownFile.mockResolvedValue({ status: 'saved', modified: 1, hash: 'saved-hash' })
 
     const documentStore = useDocumentStore()
     await documentStore.openFile('/workspace/note.md', '# note', 'note.md')
@@ -46,12 +47,43 @@ describe('documentStore actions', () => {
     const saved = await documentStore.saveFile()
 
     expect(saved).toBe(true)
-    expect(documentStoragePortMock.saveMarkdownFile).toHaveBeenCalledWith('/workspace/note.md', '# changed')
+    expect(documentStoragePortMock.saveMarkdownFile).toHaveBeenCalledWith('/workspace/note.md', '# changed', undefined)
     expect(documentStore.activeDocument?.isDirty).toBe(false)
     expect(documentStore.activeDocument?.savedContent).toBe('# changed')
     expect(typeof documentStore.lastSaveTime).toBe('number')
   })
 
+  it('sends the loaded hash with saves and reports conflicting disk changes', async () => {
+    documentStoragePortMock.saveMarkdownFile.mockResolvedValueOnce({
+      status: 'conflict',
+      content: '# edited elsewhere',
+      modified: 2,
+      hash: 'disk-hash',
+    })
+
+    const documentStore = useDocumentStore()
+    await documentStore.openFile('/workspace/note.md', '# note', 'note.md', { modified: 1, hash: 'loaded-hash' })
+    documentStore.updateContent('# changed')
+
+    const saved = await documentStore.saveFile()
+
+    expect(saved).toBe(false)
+    expect(documentStoragePortMock.saveMarkdownFile).toHaveBeenCalledWith('/workspace/note.md', '# changed', 'loaded-hash')
+    expect(documentStore.activeDocument?.isDirty).toBe(true)
+    expect(documentStore.externalChange).toMatchObject({
+      diskContent: '# edited elsewhere',
+      diskHash: 'disk-hash',
+      saveConflict: true,
+    })
+
+    documentStore.keepLocalVersion()
+    documentStoragePortMock.saveMarkdownFile.mockResolvedValueOnce({ status: 'saved', modified: 3, hash: 'new-hash' })
+
+    expect(await documentStore.saveFile()).toBe(true)
+    expect(documentStoragePortMock.saveMarkdownFile).toHaveBeenLastCalledWith('/workspace/note.md', '# changed', 'disk-hash')
+    expect(documentStore.activeDocument?.hash).toBe('new-hash')
+  })
+
   it('saves untitled documents through save-as flow', async () => {
     documentStoragePortMock.saveMarkdownFileAs.mockResolvedValue({
       path: '/workspace/new-note.md',
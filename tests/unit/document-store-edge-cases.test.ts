@@ -6,6 +6,7 @@ const documentStoragePortMock = vi.hoisted(() => ({
   openMarkdownFile: vi.fn(),
   saveMarkdownFile: vi.fn(),
   saveMarkdownFileAs: vi.fn(),
+  readLinkedImage: vi.fn(),
 }))
 
 vi.mock('../../src/platform/tauri/documentStorage', () => ({
@@ -76,7 +77,7 @@ describe('documentStore edge cases', () => {
     ]
 
     documentStoragePortMock.saveMarkdownFile
-      .mockResolvedValueOnce(undefined)
+      .mockResolvedValueOnce({ status: 'saved', modified: 1, hash: 'saved-hash' })
       .mockRejectedValueOnce(new Error('disk full'))
 
     const saved = await documentStore.saveAllFiles()
@@ -159,7 +160,11 @@ describe('documentStore edge cases', () => {
     await documentStore.openFile('/workspace/doc.md', '# original', 'doc.md')
     const consoleErrorSpy = vi.spyOn(console, 'error').mockImplementation(() => {})
 
-    documentStoragePortMock.readFile.mockRejectedValue(new Error('file does not exist'))
+    documentStoragePortMock.readFile.mockRejectedValue({
+      kind: 'not_found',
+      message: '/workspace/doc.md does not exist',
+      path: '/workspace/doc.md',
+    })
 
     await documentStore.checkActiveDocumentExternalChange()
 
@@ -277,7 +282,7 @@ describe('documentStore edge cases', () => {
       '/workspace/b.md': '# stale-b',
     }
 
-    documentStoragePortMock.saveMarkdownFile.mockResolvedValue(undefined)
+    documentStoragePortMock.saveMarkdownFile.mockResolvedValue({ status: 'saved', modified: 1, hash: 'saved-hash' })
 
     const saved = await documentStore.saveAllFiles()
 
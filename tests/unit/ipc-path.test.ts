@@ -0,0 +1,51 @@
+import { describe, expect, it } from 'vitest'
+import {
+  fromIpcPath,
+  isSameOrWithin,
+  joinPath,
+  pathName,
+  replacePathPrefix,
+  toIpcPath,
+} from '../../src/platform/tauri/ipcPath'
+
+describe('ipcPath', () => {
+  it('passes UTF-8 paths through as plain strings', () => {
+    expect(fromIpcPath('/workspace/note.md')).toBe('/workspace/note.md')
+    expect(toIpcPath('/workspace/note.md')).toBe('/workspace/note.md')
+  })
+
+  it('sends raw paths and the paths below them back with their original bytes', () => {
+    const folder = { display: '/caf\uFFFD', bytes: [0x2f, 0x63, 0x61, 0x66, 0xe9] }
+
+    expect(fromIpcPath(folder)).toBe('/caf\uFFFD')
+    expect(toIpcPath('/caf\uFFFD')).toEqual(folder)
+    expect(toIpcPath('/caf\uFFFD/n.md')).toEqual({
+      display: '/caf\uFFFD/n.md',
+      bytes: [0x2f, 0x63, 0x61, 0x66, 0xe9, 0x2f, 0x6e, 0x2e, 0x6d, 0x64],
+    })
+    expect(toIpcPath('/cafe/n.md')).toBe('/cafe/n.md')
+  })
+
+  it('extends Windows raw paths as UTF-16 code units', () => {
+    const folder = { display: 'C:\\\uFFFD', bytes: [0x43, 0, 0x3a, 0, 0x5c, 0, 0x00, 0xd8] }
+
+    fromIpcPath(folder)
+    expect(toIpcPath('C:\\\uFFFD\\a')).toEqual({
+      display: 'C:\\\uFFFD\\a',
+      bytes: [0x43, 0, 0x3a, 0, 0x5c, 0, 0x00, 0xd8, 0x5c, 0, 0x61, 0],
+    })
+  })
+
+  it('compares and rewrites paths by whole components', () => {
+    expect(isSameOrWithin('/notes/a.md', '/notes')).toBe(true)
+    expect(isSameOrWithin('C:\\notes\\a.md', 'C:\\notes')).toBe(true)
+    expect(isSameOrWithin('/notes-old/a.md', '/notes')).toBe(false)
+
+    expect(replacePathPrefix('/notes/a.md', '/notes', '/archive')).toBe('/archive/a.md')
+    expect(replacePathPrefix('/notes-old/a.md', '/notes', '/archive')).toBe('/notes-old/a.md')
+
+    expect(pathName('C:\\notes\\a.md')).toBe('a.md')
+    expect(joinPath('C:\\notes\\', 'b.md')).toBe('C:\\notes\\b.md')
+    expect(joinPath('/notes', 'b.md')).toBe('/notes/b.md')
+  })
+})
@@ -24,13 +24,28 @@ describe('tauri ports', () => {
     await tauriDocumentStoragePort.openMarkdownFile()
     expect(invokeMock).toHaveBeenCalledWith('open_markdown_file')
 
-    invokeMock.mockResolvedValueOnce(undefined)
-    await tauriDocumentStoragePort.saveMarkdownFile('/p.md', '# updated')
-    expect(invokeMock).toHaveBeenCalledWith('save_markdown_file', { path: '/p.md', content: '# updated' })
+    invokeMock.mockResolvedValueOnce({ status: 'saved', modified: 1, hash: 'new' })
+    await expect(tauriDocumentStoragePort.saveMarkdownFile('/p.md', '# updated', 'old')).resolves.toEqual({
+      status: 'saved',
+      modified: 1,
+      hash: 'new',
+    })
+    expect(invokeMock).toHaveBeenCalledWith('save_markdown_file', {
+      path: '/p.md',
+      content: '# updated',
+      expectedHash: 'old',
+    })
 
     invokeMock.mockResolvedValueOnce({ path: '/save-as.md', name: 'save-as.md' })
     await tauriDocumentStoragePort.saveMarkdownFileAs('# new')
     expect(invokeMock).toHaveBeenCalledWith('save_markdown_file_as', { content: '# new' })
+
+    invokeMock.mockResolvedValueOnce(new ArrayBuffer(0))
+    await tauriDocumentStoragePort.readLinkedImage('/p.md', '/images/a.png')
+    expect(invokeMock).toHaveBeenCalledWith('read_linked_image', {
+      document: '/p.md',
+      target: '/images/a.png',
+    })
   })
 
   it('calls invoke with workspace commands', async () => {
@@ -38,8 +53,8 @@ describe('tauri ports', () => {
     await tauriWorkspacePort.openFolderDialog()
     expect(invokeMock).toHaveBeenCalledWith('open_folder_dialog')
 
-    invokeMock.mockResolvedValueOnce([])
-    await tauriWorkspacePort.readDirectory('/workspace')
+    invokeMock.mockResolvedValueOnce({ entries: [], warnings: [] })
+    await expect(tauriWorkspacePort.readDirectory('/workspace')).resolves.toEqual([])
     expect(invokeMock).toHaveBeenCalledWith('read_directory', { path: '/workspace' })
 
     invokeMock.mockResolvedValueOnce({ path: '/workspace/new.md', content: '', name: 'new.md' })
@@ -50,22 +65,37 @@ describe('tauri ports', () => {
     await tauriWorkspacePort.createFolder('/workspace/new-folder')
     expect(invokeMock).toHaveBeenCalledWith('create_folder', { path: '/workspace/new-folder' })
 
-    invokeMock.mockResolvedValueOnce('/workspace/renamed.md')
-    await tauriWorkspacePort.renameItem('/workspace/old.md', 'renamed.md')
+    invokeMock.mockResolvedValueOnce({ path: '/workspace/renamed.md', updated_files: [] })
+    await expect(tauriWorkspacePort.renameItem('/workspace/old.md', 'renamed.md')).resolves.toBe('/workspace/renamed.md')
     expect(invokeMock).toHaveBeenCalledWith('rename_item', { oldPath: '/workspace/old.md', newName: 'renamed.md' })
 
     invokeMock.mockResolvedValueOnce(undefined)
     await tauriWorkspacePort.deleteItem('/workspace/delete.md')
     expect(invokeMock).toHaveBeenCalledWith('delete_item', { path: '/workspace/delete.md' })
 
-    invokeMock.mockResolvedValueOnce('/workspace/target/file.md')
-    await tauriWorkspacePort.moveItem('/workspace/source/file.md', '/workspace/target')
+    invokeMock.mockResolvedValueOnce({ path: '/workspace/target/file.md', updated_files: [] })
+    await expect(tauriWorkspacePort.moveItem('/workspace/source/file.md', '/workspace/target')).resolves.toBe('/workspace/target/file.md')
     expect(invokeMock).toHaveBeenCalledWith('move_item', {
       sourcePath: '/workspace/source/file.md',
       targetDir: '/workspace/target',
     })
   })
 
+  it('shows raw paths by their display form and sends them back as raw bytes', async () => {
+    const raw = { display: '/workspace/caf\uFFFD', bytes: [0x2f, 0x77, 0x73, 0xe9] }
+    invokeMock.mockResolvedValueOnce({
+      entries: [{ name: 'caf\uFFFD', path: raw, is_dir: true, is_markdown: false }],
+      warnings: [],
+    })
+
+    const entries = await tauriWorkspacePort.readDirectory('/workspace')
+    expect(entries[0].path).toBe('/workspace/caf\uFFFD')
+
+    invokeMock.mockResolvedValueOnce({ entries: [], warnings: [] })
+    await tauriWorkspacePort.readDirectory(entries[0].path)
+    expect(invokeMock).toHaveBeenLastCalledWith('read_directory', { path: raw })
+  })
+
   it('calls invoke with file watch commands', async () => {
     invokeMock.mockResolvedValueOnce(undefined)
     await tauriFileWatchPort.startFileWatch('/workspace/note.md')
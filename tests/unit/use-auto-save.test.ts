@@ -8,6 +8,7 @@ const documentStoragePortMock = vi.hoisted(() => ({
   openMarkdownFile: vi.fn(),
   saveMarkdownFile: vi.fn(),
   saveMarkdownFileAs: vi.fn(),
+  readLinkedImage: vi.fn(),
 }))
 
 vi.mock('../../src/platform/tauri/documentStorage', () => ({
@@ -44,7 +45,7 @@ describe('useAutoSave', () => {
   })
 
   it('auto-saves dirty files with a path and resets status to idle', async () => {
-    documentStoragePortMock.saveMarkdownFile.mockResolvedValue(undefined)
+    documentStoragePortMock.saveMarkdownFile.mockResolvedValue({ status: 'saved', modified: 1, hash: 'saved-hash' })
     const documentStore = useDocumentStore()
     await documentStore.openFile('/workspace/note.md', '# note', 'note.md')
 
@@ -63,7 +64,7 @@ describe('useAutoSave', () => {
       lastSaveError: string | null
     }
 
-    expect(documentStoragePortMock.saveMarkdownFile).toHaveBeenCalledWith('/workspace/note.md', '# changed')
+    expect(documentStoragePortMock.saveMarkdownFile).toHaveBeenCalledWith('/workspace/note.md', '# changed', undefined)
     expect(vm.saveStatus).toBe('saved')
     expect(vm.lastSaveError).toBeNull()
     expect(documentStore.activeDocument?.isDirty).toBe(false)
@@ -128,7 +129,7 @@ describe('useAutoSave', () => {
   })
 
   it('does not trigger auto-save when file content has not changed', async () => {
-    documentStoragePortMock.saveMarkdownFile.mockResolvedValue(undefined)
+    documentStoragePortMock.saveMarkdownFile.mockResolvedValue({ status: 'saved', modified: 1, hash: 'saved-hash' })
     const documentStore = useDocumentStore()
     await documentStore.openFile('/workspace/stable.md', '# stable', 'stable.md')
 